@@ -0,0 +1,101 @@
+//! Benchmarks of the board and search code that tournaments spend their
+//! time in, each on positions with 10, 25 and 40 of the standard board's
+//! 61 cells filled.
+//!
+//! Run with `cargo bench`.  To compare a change against the commit before
+//! it, run `cargo bench -- --save-baseline before` on the old commit and
+//! `cargo bench -- --baseline before` on the new one.  The depth 5 searches
+//! take most of the time; `cargo bench -- choose/depth-2` and the like run
+//! a single benchmark.
+
+extern crate hexgame;
+#[macro_use]
+extern crate criterion;
+extern crate rand;
+
+use criterion::{black_box, BenchmarkId, Criterion, SamplingMode};
+use hexgame::{Config, GameState, MoveResult, Player};
+use hexgame::ai::{Ai, FeatureRanker, RankerAi};
+use hexgame::training::seeded_rng;
+use rand::Rng;
+
+const FILLS: &'static [usize] = &[10, 25, 40];
+
+// A position with `fill` stones from random moves that don't end the game,
+// the same every run.
+fn position(fill: usize) -> GameState {
+    let mut rng = seeded_rng(fill);
+    'game: loop {
+        let mut state = GameState::with_config(Config::standard());
+        while state.map().iter().count() < fill {
+            let moves: Vec<_> = state.legal_moves().into_iter()
+                .filter(|mv| state.with_move(mv).is_over() == MoveResult::Good)
+                .collect();
+            match rng.choose(&moves) {
+                Some(mv) => state = state.with_move(mv),
+                None => continue 'game,
+            }
+        }
+        return state;
+    }
+}
+
+fn board(c: &mut Criterion) {
+    let mut group = c.benchmark_group("with_move");
+    for &fill in FILLS {
+        let state = position(fill);
+        let mv = state.legal_moves()[0];
+        group.bench_with_input(BenchmarkId::from_parameter(fill), &state, |b, state| {
+            b.iter(|| state.with_move(black_box(&mv)))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("is_over");
+    for &fill in FILLS {
+        group.bench_with_input(BenchmarkId::from_parameter(fill), &position(fill), |b, state| {
+            b.iter(|| black_box(state).is_over())
+        });
+    }
+    group.finish();
+}
+
+fn features(c: &mut Criterion) {
+    let counts: [(&str, fn(&GameState, Player) -> u32); 4] = [
+        ("count_triads", FeatureRanker::count_triads),
+        ("count_windows", FeatureRanker::count_windows),
+        ("count_slots", FeatureRanker::count_slots),
+        ("count_doubles", FeatureRanker::count_doubles),
+    ];
+    for &(name, count) in &counts {
+        let mut group = c.benchmark_group(name);
+        for &fill in FILLS {
+            group.bench_with_input(BenchmarkId::from_parameter(fill), &position(fill), |b, state| {
+                b.iter(|| count(black_box(state), state.current_player()))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("choose");
+    // Deep searches take seconds, so time a few runs of each rather than
+    // the many criterion would like.
+    group.sample_size(10);
+    group.sampling_mode(SamplingMode::Flat);
+    for depth in 2 .. 6 {
+        for &fill in FILLS {
+            let state = position(fill);
+            let id = BenchmarkId::new(format!("depth-{}", depth), fill);
+            group.bench_with_input(id, &state, |b, state| {
+                let mut ai = RankerAi::new(FeatureRanker::default(), depth);
+                b.iter(|| ai.choose(black_box(state), state.current_player()))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, board, features, search);
+criterion_main!(benches);
@@ -0,0 +1,184 @@
+//! The fully connected layers the network evaluators are built from, and
+//! the text format their weights are saved in.
+//!
+//! A weight file starts with a `KIND RADIUS HIDDEN` line naming the network
+//! and its size, followed by each layer in turn, one unit a line: the unit's
+//! weights, then its bias.  Blank lines and lines starting with `#` are
+//! skipped.
+
+use super::*;
+use rand::Rng;
+
+/// `units` linear units over `inputs` inputs.  Activation functions are up
+/// to the network.
+#[derive(Clone)]
+pub struct Layer {
+    inputs: usize,
+    units: usize,
+    /// `units` rows of `inputs` weights.
+    pub weights: Vec<f32>,
+    pub bias: Vec<f32>,
+}
+
+impl Layer {
+    pub fn new(inputs: usize, units: usize) -> Layer {
+        Layer {
+            inputs: inputs,
+            units: units,
+            weights: vec![0.0; inputs * units],
+            bias: vec![0.0; units],
+        }
+    }
+
+    /// Weights drawn uniformly from +-1/sqrt(inputs), and zero biases.
+    pub fn random<R: Rng>(inputs: usize, units: usize, rng: &mut R) -> Layer {
+        let mut layer = Layer::new(inputs, units);
+        let scale = 1.0 / (inputs as f32).sqrt();
+        for w in &mut layer.weights {
+            *w = rng.gen_range(-scale, scale);
+        }
+        layer
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    pub fn units(&self) -> usize {
+        self.units
+    }
+
+    /// The weights into `unit`.
+    pub fn row(&self, unit: usize) -> &[f32] {
+        &self.weights[unit * self.inputs .. (unit + 1) * self.inputs]
+    }
+
+    /// The units' sums for `input`, skipping inputs that are zero.
+    pub fn dense(&self, input: &[f32]) -> Vec<f32> {
+        (0 .. self.units).map(|unit| {
+            let sum: f32 = input.iter().zip(self.row(unit)).filter(|&(&x, _)| x != 0.0).map(|(x, w)| x * w).sum();
+            sum + self.bias[unit]
+        }).collect()
+    }
+
+    /// The units' sums for an input that is one at `active` and zero
+    /// everywhere else.
+    pub fn sparse(&self, active: &[usize]) -> Vec<f32> {
+        (0 .. self.units).map(|unit| {
+            let row = self.row(unit);
+            active.iter().map(|&i| row[i]).sum::<f32>() + self.bias[unit]
+        }).collect()
+    }
+
+    /// `dense` for many inputs, a unit at a time so that each row of
+    /// weights is read once.
+    pub fn dense_batch(&self, inputs: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        let mut out = vec![vec![0.0; self.units]; inputs.len()];
+        for unit in 0 .. self.units {
+            let row = self.row(unit);
+            for (input, sums) in inputs.iter().zip(&mut out) {
+                let sum: f32 = input.iter().zip(row).filter(|&(&x, _)| x != 0.0).map(|(x, w)| x * w).sum();
+                sums[unit] = sum + self.bias[unit];
+            }
+        }
+        out
+    }
+
+    /// `sparse` for many inputs, a unit at a time.
+    pub fn sparse_batch(&self, actives: &[Vec<usize>]) -> Vec<Vec<f32>> {
+        let mut out = vec![vec![0.0; self.units]; actives.len()];
+        for unit in 0 .. self.units {
+            let row = self.row(unit);
+            for (active, sums) in actives.iter().zip(&mut out) {
+                sums[unit] = active.iter().map(|&i| row[i]).sum::<f32>() + self.bias[unit];
+            }
+        }
+        out
+    }
+
+    /// Takes a gradient descent step of `step` times `gradient`, a layer of
+    /// the same shape.
+    pub fn descend(&mut self, gradient: &Layer, step: f32) {
+        for (w, g) in self.weights.iter_mut().zip(&gradient.weights) {
+            *w -= step * g;
+        }
+        for (b, g) in self.bias.iter_mut().zip(&gradient.bias) {
+            *b -= step * g;
+        }
+    }
+
+    // The number of values the layer takes up in a weight file.
+    fn len(&self) -> usize {
+        self.units * (self.inputs + 1)
+    }
+
+    // Fills the layer from the start of `values`, returning the rest.
+    fn read<'a>(&mut self, values: &'a [f32]) -> &'a [f32] {
+        let (mine, rest) = values.split_at(self.len());
+        for (unit, line) in mine.chunks(self.inputs + 1).enumerate() {
+            self.weights[unit * self.inputs .. (unit + 1) * self.inputs].copy_from_slice(&line[.. self.inputs]);
+            self.bias[unit] = line[self.inputs];
+        }
+        rest
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for unit in 0 .. self.units {
+            for w in self.row(unit) {
+                write!(f, "{} ", w)?;
+            }
+            writeln!(f, "{}", self.bias[unit])?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the header of a weight file of `kind`, returning the radius and
+/// hidden size, and the numbers that follow it.
+pub fn parse_weights(s: &str, kind: &str) -> Result<(u32, usize, Vec<f32>), String> {
+    let mut lines = s.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#'));
+    let mut header = lines.next().unwrap_or("").split_whitespace();
+    let (radius, hidden) = match (header.next(), header.next(), header.next(), header.next()) {
+        (Some(k), Some(radius), Some(hidden), None) if k == kind => {
+            let radius = radius.parse().map_err(|e| format!("bad radius: {}", e))?;
+            let hidden = hidden.parse().map_err(|e| format!("bad hidden size: {}", e))?;
+            (radius, hidden)
+        }
+        _ => return Err(format!("expected `{} RADIUS HIDDEN`", kind)),
+    };
+    if radius < 1 || radius > MAX_RADIUS {
+        return Err(format!("bad radius: {}", radius));
+    }
+
+    let mut values = vec![];
+    for line in lines {
+        for word in line.split_whitespace() {
+            values.push(word.parse::<f32>().map_err(|e| format!("bad weight {}: {}", word, e))?);
+        }
+    }
+    Ok((radius, hidden, values))
+}
+
+/// Fills `layers` in order from `values`, which must hold exactly enough.
+pub fn read_layers(layers: &mut [&mut Layer], values: &[f32]) -> Result<(), String> {
+    let expected: usize = layers.iter().map(|l| l.len()).sum();
+    if values.len() != expected {
+        return Err(format!("expected {} weights, found {}", expected, values.len()));
+    }
+    let mut rest = values;
+    for layer in layers {
+        rest = layer.read(rest);
+    }
+    Ok(())
+}
+
+/// The contents of the file at `path`.
+pub fn read_file(path: &str) -> Result<String, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path, e))?;
+    Ok(contents)
+}
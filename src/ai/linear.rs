@@ -0,0 +1,182 @@
+use super::*;
+
+/// The number of distinct line patterns, and so of weights.
+pub const PATTERNS: usize = 44;
+
+const LENGTH: usize = 4;
+
+/// A linear evaluator over every four cell line on the board.  Each line is
+/// classified by its contents as seen by one player (`x` for their stones,
+/// `o` for the opponent's and `.` for empty cells), a line and its reverse
+/// being the same pattern, and the rank is the weighted count of each
+/// pattern.  The empty line has no weight.
+///
+/// Unlike `FeatureRanker` nothing is known about which patterns matter, so
+/// the weights have to be learned; see `training::td_game`.
+#[derive(Clone)]
+pub struct LinearRanker {
+    pub weights: Vec<f32>,
+    slots: [Option<usize>; 81],
+    lines: Vec<[HexPosition; LENGTH]>,
+    radius: Option<u32>,
+}
+
+fn pattern_name(mut index: usize) -> String {
+    let mut name = String::new();
+    for _ in 0 .. LENGTH {
+        name.push(match index % 3 {
+            0 => '.',
+            1 => 'x',
+            _ => 'o',
+        });
+        index /= 3;
+    }
+    name
+}
+
+fn reverse(mut index: usize) -> usize {
+    let mut out = 0;
+    for _ in 0 .. LENGTH {
+        out = out * 3 + index % 3;
+        index /= 3;
+    }
+    out
+}
+
+// Maps each of the 3^4 raw lines onto its pattern's weight.
+fn slots() -> [Option<usize>; 81] {
+    let mut slots = [None; 81];
+    let mut next = 0;
+    for index in 1 .. 81 {
+        let canonical = ::std::cmp::min(index, reverse(index));
+        if canonical == index {
+            slots[index] = Some(next);
+            next += 1;
+        } else {
+            slots[index] = slots[canonical];
+        }
+    }
+    slots
+}
+
+impl LinearRanker {
+    /// A ranker with every weight zero, which knows nothing but the rules.
+    pub fn new() -> LinearRanker {
+        LinearRanker::with_weights(vec![0.0; PATTERNS])
+    }
+
+    pub fn with_weights(weights: Vec<f32>) -> LinearRanker {
+        assert_eq!(weights.len(), PATTERNS);
+        LinearRanker {
+            weights: weights,
+            slots: slots(),
+            lines: vec![],
+            radius: None,
+        }
+    }
+
+    /// The names of the patterns, in the order of the weights.
+    pub fn pattern_names() -> Vec<String> {
+        let slots = slots();
+        (1 .. 81).filter(|&i| slots[i].is_some() && i <= reverse(i)).map(pattern_name).collect()
+    }
+
+    /// Reads weights written as `pattern = value` lines, as written by this
+    /// type's `Display` impl.  Missing patterns are weighted zero.
+    pub fn parse(s: &str) -> Result<LinearRanker, String> {
+        let names = LinearRanker::pattern_names();
+        let mut ranker = LinearRanker::new();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=').map(str::trim);
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => return Err(format!("bad weight line: {}", line)),
+            };
+            let value: f32 = value.parse().map_err(|e| format!("bad weight {}: {}", name, e))?;
+            let reversed: String = name.chars().rev().collect();
+            match names.iter().position(|n| *n == name || *n == reversed) {
+                Some(i) => ranker.weights[i] = value,
+                None => return Err(format!("unknown pattern: {}", name)),
+            }
+        }
+        Ok(ranker)
+    }
+
+    pub fn load(path: &str) -> Result<LinearRanker, String> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|e| format!("couldn't read {}: {}", path, e))?;
+        LinearRanker::parse(&contents)
+    }
+
+    fn prepare(&mut self, config: Config) {
+        if self.radius == Some(config.radius) {
+            return;
+        }
+        let grid = HexGrid::new(config.radius);
+        let map: Map<Player, HexGrid> = Map::new(HexGrid::new(config.radius));
+        self.lines.clear();
+        for pos in grid.iter() {
+            for ray in pos.rays().iter().cloned().take(3) {
+                let cells: Vec<_> = ray.take(LENGTH).collect();
+                if cells.iter().all(|c| map.could_contain(c)) {
+                    self.lines.push([cells[0], cells[1], cells[2], cells[3]]);
+                }
+            }
+        }
+        self.radius = Some(config.radius);
+    }
+
+    /// How many times each pattern appears on the board for `player`.
+    pub fn features(&mut self, state: &GameState, player: Player) -> Vec<f32> {
+        self.prepare(state.config());
+        let mut counts = vec![0.0; PATTERNS];
+        for line in &self.lines {
+            let mut index = 0;
+            for cell in line.iter().rev() {
+                index = index * 3 + match state.map().get(cell) {
+                    None => 0,
+                    Some(&p) if p == player => 1,
+                    Some(_) => 2,
+                };
+            }
+            if let Some(slot) = self.slots[index] {
+                counts[slot] += 1.0;
+            }
+        }
+        counts
+    }
+}
+
+impl Ranker for LinearRanker {
+    fn rank(&mut self, state: &GameState, player: Player) -> f32 {
+        match state.is_over() {
+            MoveResult::End(p) if p == player => return WIN,
+            MoveResult::End(p) if p != player => return LOSS,
+            MoveResult::Tie => return -100.0,
+            _ => {  }
+        }
+
+        let features = self.features(state, player);
+        self.weights.iter().zip(&features).map(|(w, x)| w * x).sum()
+    }
+}
+
+impl fmt::Display for LinearRanker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, weight) in LinearRanker::pattern_names().iter().zip(&self.weights) {
+            writeln!(f, "{} = {}", name, weight)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for LinearRanker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LinearRanker")
+    }
+}
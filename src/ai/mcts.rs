@@ -0,0 +1,156 @@
+use super::*;
+use rand::{self, Rng};
+use ::std::time::{Duration, Instant};
+
+/// Monte-Carlo tree search with UCT selection and uniformly random playouts.
+#[derive(Debug, Clone, Copy)]
+pub struct MctsAi {
+    iterations: usize,
+    pub exploration: f32,
+}
+
+struct Node {
+    position: Option<HexPosition>,
+    // The player whose move led to this node.
+    player: Player,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<HexPosition>,
+    visits: u32,
+    score: f32,
+}
+
+fn open_moves(state: &GameState) -> Vec<HexPosition> {
+    if state.is_over() != MoveResult::Good {
+        return vec![];
+    }
+    state.legal_moves()
+}
+
+fn reward(result: MoveResult, player: Player) -> f32 {
+    match result {
+        MoveResult::End(p) if p == player => 1.0,
+        MoveResult::Tie => 0.5,
+        _ => 0.0,
+    }
+}
+
+impl MctsAi {
+    pub fn new(iterations: usize) -> MctsAi {
+        MctsAi {
+            iterations: ::std::cmp::max(iterations, 1),
+            exploration: 1.4,
+        }
+    }
+
+    fn search(&mut self, state: &GameState, player: Player, limit: usize, deadline: Option<Instant>) -> Vec<Node> {
+        let mut rng = rand::thread_rng();
+        let mut tree = vec![Node {
+            position: None,
+            player: player.inverse(),
+            parent: None,
+            children: vec![],
+            untried: open_moves(state),
+            visits: 0,
+            score: 0.0,
+        }];
+
+        for i in 0 .. limit {
+            if let Some(deadline) = deadline {
+                if i > 0 && Instant::now() >= deadline {
+                    break;
+                }
+            }
+
+            let mut node = 0;
+            let mut state = state.clone();
+
+            // selection
+            while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+                let ln_visits = (tree[node].visits as f32).ln();
+                let exploration = self.exploration;
+                let uct = |n: &Node| {
+                    n.score / n.visits as f32 + exploration * (ln_visits / n.visits as f32).sqrt()
+                };
+                let mut best = tree[node].children[0];
+                for &child in &tree[node].children {
+                    if uct(&tree[child]) > uct(&tree[best]) {
+                        best = child;
+                    }
+                }
+                node = best;
+                state.make_move(&tree[node].position.unwrap());
+            }
+
+            // expansion
+            if !tree[node].untried.is_empty() {
+                let idx = rng.gen_range(0, tree[node].untried.len());
+                let mv = tree[node].untried.swap_remove(idx);
+                let mover = state.current_player();
+                state.make_move(&mv);
+                let child = Node {
+                    position: Some(mv),
+                    player: mover,
+                    parent: Some(node),
+                    children: vec![],
+                    untried: open_moves(&state),
+                    visits: 0,
+                    score: 0.0,
+                };
+                tree.push(child);
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                node = child;
+            }
+
+            // playout
+            let mut result = state.is_over();
+            // Only empty cells: a swap drawn after Green's first move would
+            // no longer be legal.
+            let mut moves: Vec<_> = open_moves(&state).into_iter().filter(|mv| !state.is_swap(mv)).collect();
+            rng.shuffle(&mut moves);
+            while result == MoveResult::Good {
+                let mv = moves.pop().unwrap();
+                result = state.make_move(&mv);
+            }
+
+            // backpropagation
+            let mut cur = Some(node);
+            while let Some(n) = cur {
+                tree[n].visits += 1;
+                tree[n].score += reward(result, tree[n].player);
+                cur = tree[n].parent;
+            }
+        }
+
+        tree
+    }
+
+    fn most_visited(tree: &[Node]) -> HexPosition {
+        let best = tree[0].children.iter().max_by_key(|&&c| tree[c].visits).unwrap();
+        tree[*best].position.unwrap()
+    }
+}
+
+impl Ai for MctsAi {
+    fn choose(&mut self, state: &GameState, player: Player) -> HexPosition {
+        let limit = self.iterations;
+        let tree = self.search(state, player, limit, None);
+        MctsAi::most_visited(&tree)
+    }
+
+    fn choose_timed(&mut self, state: &GameState, player: Player, budget: Duration) -> HexPosition {
+        let tree = self.search(state, player, ::std::usize::MAX, Some(Instant::now() + budget));
+        MctsAi::most_visited(&tree)
+    }
+
+    /// Scores moves by their share of the root's visits.
+    fn score_moves(&mut self, state: &GameState, player: Player) -> Vec<(HexPosition, f32)> {
+        let limit = self.iterations;
+        let tree = self.search(state, player, limit, None);
+        let total = tree[0].visits as f32;
+        tree[0].children.iter()
+            .map(|&c| (tree[c].position.unwrap(), tree[c].visits as f32 / total))
+            .collect()
+    }
+}
@@ -0,0 +1,151 @@
+use super::*;
+use super::layer::{self, Layer};
+use rand::Rng;
+
+/// A small neural network evaluator: one hidden layer of `tanh` units over
+/// the board, seen from one player's side.  The inputs are one per cell
+/// holding that player's stone, one per cell holding the opponent's, and
+/// one that is set when it's that player's turn.  The output is the log
+/// odds of that player winning.
+///
+/// Networks are trained from self-play records by `training::fit_mlp`.
+#[derive(Clone)]
+pub struct MlpRanker {
+    radius: u32,
+    pub hidden_layer: Layer,
+    /// A single unit.
+    pub output: Layer,
+    cells: Vec<HexPosition>,
+}
+
+/// The intermediate values of one forward pass, kept for training.
+pub struct Activations {
+    pub input: Vec<f32>,
+    pub hidden: Vec<f32>,
+    pub output: f32,
+}
+
+impl MlpRanker {
+    /// An untrained network for a board of `radius`, with `hidden` hidden
+    /// units.
+    pub fn random<R: Rng>(radius: u32, hidden: usize, rng: &mut R) -> MlpRanker {
+        let mut net = MlpRanker::zeroed(radius, hidden);
+        net.hidden_layer = Layer::random(net.inputs(), hidden, rng);
+        net.output = Layer::random(hidden, 1, rng);
+        net
+    }
+
+    fn zeroed(radius: u32, hidden: usize) -> MlpRanker {
+        let cells: Vec<_> = HexGrid::new(radius).iter().collect();
+        let inputs = 2 * cells.len() + 1;
+        MlpRanker {
+            radius: radius,
+            hidden_layer: Layer::new(inputs, hidden),
+            output: Layer::new(hidden, 1),
+            cells: cells,
+        }
+    }
+
+    pub fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    pub fn inputs(&self) -> usize {
+        2 * self.cells.len() + 1
+    }
+
+    pub fn hidden(&self) -> usize {
+        self.hidden_layer.units()
+    }
+
+    /// Reads a network saved by `Display`, an `mlp` weight file as described
+    /// in `ai::layer` with the hidden layer and then the output unit.
+    pub fn parse(s: &str) -> Result<MlpRanker, String> {
+        let (radius, hidden, values) = layer::parse_weights(s, "mlp")?;
+        let mut net = MlpRanker::zeroed(radius, hidden);
+        layer::read_layers(&mut [&mut net.hidden_layer, &mut net.output], &values)?;
+        Ok(net)
+    }
+
+    pub fn load(path: &str) -> Result<MlpRanker, String> {
+        MlpRanker::parse(&layer::read_file(path)?)
+    }
+
+    /// The network's inputs for `state` from `player`'s side.  Cells off
+    /// the network's board are ignored.
+    pub fn encode(&self, state: &GameState, player: Player) -> Vec<f32> {
+        let n = self.cells.len();
+        let mut input = vec![0.0; self.inputs()];
+        for (i, cell) in self.cells.iter().enumerate() {
+            match state.map().get(cell) {
+                Some(&p) if p == player => input[i] = 1.0,
+                Some(_) => input[n + i] = 1.0,
+                None => {}
+            }
+        }
+        if state.current_player() == player {
+            input[2 * n] = 1.0;
+        }
+        input
+    }
+
+    pub fn forward(&self, input: Vec<f32>) -> Activations {
+        let hidden: Vec<f32> = self.hidden_layer.dense(&input).into_iter().map(f32::tanh).collect();
+        let output = self.output.dense(&hidden)[0];
+        Activations {
+            input: input,
+            hidden: hidden,
+            output: output,
+        }
+    }
+
+    /// The outputs for many inputs at once, a layer at a time.
+    pub fn forward_batch(&self, inputs: &[Vec<f32>]) -> Vec<f32> {
+        let mut hidden = self.hidden_layer.dense_batch(inputs);
+        for h in hidden.iter_mut().flat_map(|h| h.iter_mut()) {
+            *h = h.tanh();
+        }
+        self.output.dense_batch(&hidden).into_iter().map(|o| o[0]).collect()
+    }
+
+    /// `rank` for many positions at once.
+    pub fn rank_batch(&self, states: &[GameState], player: Player) -> Vec<f32> {
+        let inputs: Vec<_> = states.iter().map(|s| self.encode(s, player)).collect();
+        let outputs = self.forward_batch(&inputs);
+        states.iter().zip(outputs).map(|(state, output)| {
+            terminal_rank(state, player).unwrap_or(output)
+        }).collect()
+    }
+}
+
+fn terminal_rank(state: &GameState, player: Player) -> Option<f32> {
+    match state.is_over() {
+        MoveResult::End(p) if p == player => Some(WIN),
+        MoveResult::End(p) if p != player => Some(LOSS),
+        MoveResult::Tie => Some(-100.0),
+        _ => None,
+    }
+}
+
+impl Ranker for MlpRanker {
+    fn rank(&mut self, state: &GameState, player: Player) -> f32 {
+        if let Some(rank) = terminal_rank(state, player) {
+            return rank;
+        }
+        let input = self.encode(state, player);
+        self.forward(input).output
+    }
+}
+
+impl fmt::Display for MlpRanker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "mlp {} {}", self.radius, self.hidden())?;
+        write!(f, "{}{}", self.hidden_layer, self.output)
+    }
+}
+
+impl fmt::Debug for MlpRanker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MlpRanker({}, {})", self.radius, self.hidden())
+    }
+}
@@ -2,12 +2,213 @@ use super::*;
 use hexagon::HexPosition;
 use hexagon::grid::{Grid, HexGrid, Map};
 use ::std::f32::{INFINITY, NEG_INFINITY};
+use ::std::fmt;
+use ::std::fs::File;
+use ::std::io::Read;
+use ::std::time::{Duration, Instant};
+use clock::Clock;
+use solver::{Endgame, Tablebase};
+
+pub mod layer;
+pub mod linear;
+pub mod mcts;
+pub mod mlp;
+pub mod tablebase;
+pub mod zero;
+
+pub use self::linear::LinearRanker;
+pub use self::mcts::MctsAi;
+pub use self::mlp::MlpRanker;
+pub use self::tablebase::{TablebaseAi, TablebaseRanker};
+pub use self::zero::{ZeroAi, ZeroNet};
 
 const WIN: f32 = INFINITY;
 const LOSS: f32 = NEG_INFINITY;
 
 pub trait Ai {
     fn choose(&mut self, state: &GameState, player: Player) -> HexPosition;
+
+    /// Like `choose`, but tries to answer within `budget`.  AIs that can't
+    /// trade strength for time just ignore it.
+    fn choose_timed(&mut self, state: &GameState, player: Player, budget: Duration) -> HexPosition {
+        let _ = budget;
+        self.choose(state, player)
+    }
+
+    /// Like `choose_timed`, with the budget worked out from the time left
+    /// on `clock`.
+    fn choose_clocked(&mut self, state: &GameState, player: Player, clock: &Clock) -> HexPosition {
+        let budget = clock.budget(state, player);
+        self.choose_timed(state, player, budget)
+    }
+
+    /// Decides what to do on `player`'s turn.  Besides choosing a move, AIs
+    /// may resign or answer a draw offer; by default they just play on.
+    fn act(&mut self, state: &GameState, player: Player) -> Action {
+        Action::Place(self.choose(state, player))
+    }
+
+    /// The AI's opinion of every open cell as a move for `player`; higher is
+    /// better.  Scores are only comparable within one call.  AIs without a
+    /// meaningful per-move score return nothing.
+    fn score_moves(&mut self, state: &GameState, player: Player) -> Vec<(HexPosition, f32)> {
+        let _ = (state, player);
+        vec![]
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MoveStatus {
+    /// Playing here makes four in a row.
+    Win,
+    /// Playing here makes three in a row, so the cell is poison.
+    Lose,
+    /// The opponent would win by playing here.
+    Block,
+    Neutral,
+}
+
+pub fn move_status(state: &GameState, pos: &HexPosition, player: Player) -> MoveStatus {
+    let mut mine = state.clone();
+    mine.current_player = player;
+    match mine.make_move(pos) {
+        MoveResult::End(p) if p == player => return MoveStatus::Win,
+        MoveResult::End(_) => return MoveStatus::Lose,
+        _ => {}
+    }
+
+    let mut theirs = state.clone();
+    theirs.current_player = player.inverse();
+    match theirs.make_move(pos) {
+        MoveResult::End(p) if p != player => MoveStatus::Block,
+        _ => MoveStatus::Neutral,
+    }
+}
+
+/// Maps `value` within `min ..= max` onto a blue (worst) to yellow (best)
+/// gradient.  Infinite values are clamped to the ends.
+pub fn heat_color(value: f32, min: f32, max: f32) -> [f32; 4] {
+    let t = if max > min { (value - min) / (max - min) } else { 0.5 };
+    let t = t.max(0.0).min(1.0);
+    [t, t, 1.0 - t, 1.0]
+}
+
+/// Colours each scored move by `heat_color`, scaled to the finite scores.
+pub fn heatmap(scores: &[(HexPosition, f32)]) -> Vec<(HexPosition, [f32; 4])> {
+    let finite = scores.iter().map(|&(_, s)| s).filter(|s| s.is_finite());
+    let (min, max) = finite.fold((INFINITY, NEG_INFINITY), |(lo, hi), s| (lo.min(s), hi.max(s)));
+    scores.iter().map(|&(pos, s)| (pos, heat_color(s, min, max))).collect()
+}
+
+/// Where the per-cell colours drawn over the board by `play` and `vis` come
+/// from: `status` for `move_status`, or any AI spec for `Ai::score_moves`.
+pub enum HeatmapSource {
+    Status,
+    Ai(Box<dyn Ai>),
+}
+
+impl HeatmapSource {
+    pub fn from_spec(spec: &str) -> Result<HeatmapSource, String> {
+        match spec {
+            "status" => Ok(HeatmapSource::Status),
+            spec => from_spec(spec).map(HeatmapSource::Ai),
+        }
+    }
+
+    pub fn heatmap(&mut self, state: &GameState) -> Vec<(HexPosition, [f32; 4])> {
+        if state.is_over() != MoveResult::Good {
+            return vec![];
+        }
+        match *self {
+            HeatmapSource::Status => status_heatmap(state, state.current_player()),
+            HeatmapSource::Ai(ref mut ai) => heatmap(&ai.score_moves(state, state.current_player())),
+        }
+    }
+}
+
+/// The heatmap of `move_status` for every open cell.
+pub fn status_heatmap(state: &GameState, player: Player) -> Vec<(HexPosition, [f32; 4])> {
+    state.map().grid().iter().filter(|pos| !state.map().contains(pos)).map(|pos| {
+        let color = match move_status(state, &pos, player) {
+            MoveStatus::Win => [0.0, 0.8, 0.0, 1.0],
+            MoveStatus::Lose => [0.3, 0.0, 0.3, 1.0],
+            MoveStatus::Block => [1.0, 0.6, 0.0, 1.0],
+            MoveStatus::Neutral => [1.0, 1.0, 1.0, 1.0],
+        };
+        (pos, color)
+    }).collect()
+}
+
+/// Builds an AI from a short description, as given on the command line:
+///
+/// * `ranker:DEPTH[:WEIGHTS_FILE]` - a `RankerAi` using a `FeatureRanker`
+/// * `linear:DEPTH[:WEIGHTS_FILE]` - a `RankerAi` using a `LinearRanker`
+/// * `mlp:DEPTH:WEIGHTS_FILE` - a `RankerAi` using an `MlpRanker`
+/// * `null:DEPTH` - a `RankerAi` using the `NullRanker`
+/// * `mcts:ITERATIONS` - an `MctsAi`
+/// * `zero:ITERATIONS:WEIGHTS_FILE` - a `ZeroAi`
+/// * `tablebase:FILE:SPEC` - the AI given by `SPEC`, playing perfectly
+///   once no more empty cells are left than the tablebase `FILE` was
+///   generated for.  The `RankerAi`s also rank such positions exactly
+///   wherever their search reaches them.
+pub fn from_spec(spec: &str) -> Result<Box<dyn Ai>, String> {
+    build(spec, None)
+}
+
+// A `RankerAi` over `ranker`, looking positions up in `table` if there is
+// one.
+fn ranker_ai<R: Ranker + 'static>(ranker: R, depth: usize, table: Option<&Tablebase>) -> Box<dyn Ai> {
+    match table {
+        Some(table) => Box::new(RankerAi::new(TablebaseRanker::new(Endgame::new(table), ranker), depth)),
+        None => Box::new(RankerAi::new(ranker, depth)),
+    }
+}
+
+// `from_spec`, with the tablebase of an enclosing `tablebase:` spec.
+fn build(spec: &str, table: Option<&Tablebase>) -> Result<Box<dyn Ai>, String> {
+    let parts: Vec<_> = spec.splitn(3, ':').collect();
+    let number = |i: usize, default: usize| -> Result<usize, String> {
+        match parts.get(i) {
+            Some(n) => n.parse().map_err(|e| format!("bad number in {}: {}", spec, e)),
+            None => Ok(default),
+        }
+    };
+
+    match parts[0] {
+        "ranker" => {
+            let ranker = match parts.get(2) {
+                Some(path) => FeatureRanker::load(path)?,
+                None => FeatureRanker::default(),
+            };
+            Ok(ranker_ai(ranker, number(1, 4)?, table))
+        }
+        "linear" => {
+            let ranker = match parts.get(2) {
+                Some(path) => LinearRanker::load(path)?,
+                None => LinearRanker::new(),
+            };
+            Ok(ranker_ai(ranker, number(1, 4)?, table))
+        }
+        "mlp" => {
+            let path = parts.get(2).ok_or(format!("missing weights file in {}", spec))?;
+            Ok(ranker_ai(MlpRanker::load(path)?, number(1, 4)?, table))
+        }
+        "null" => Ok(ranker_ai(NullRanker, number(1, 4)?, table)),
+        "mcts" => Ok(Box::new(MctsAi::new(number(1, 10_000)?))),
+        "tablebase" => match (parts.get(1), parts.get(2)) {
+            (Some(path), Some(inner)) => {
+                let table = Tablebase::load(path)?;
+                let inner = build(inner, Some(&table))?;
+                Ok(Box::new(TablebaseAi::new(Endgame::new(&table), inner)))
+            }
+            _ => Err(format!("expected tablebase:FILE:SPEC in {}", spec)),
+        },
+        "zero" => {
+            let path = parts.get(2).ok_or(format!("missing weights file in {}", spec))?;
+            Ok(Box::new(ZeroAi::new(ZeroNet::load(path)?, number(1, 400)?)))
+        }
+        _ => Err(format!("unknown ai: {}", spec)),
+    }
 }
 
 pub trait Ranker {
@@ -17,14 +218,18 @@ pub trait Ranker {
 #[derive(Debug, Clone, Copy)]
 pub struct RankerAi<R: Ranker> {
     recursion_limit: usize,
-    pub ranker: R
+    pub ranker: R,
+    /// Resign when the search scores our position at or below this.  A
+    /// threshold of `NEG_INFINITY` resigns only once a loss is proven.
+    pub resign_below: Option<f32>,
 }
 
 impl <R: Ranker> RankerAi<R> {
     pub fn new(ranker: R, recursion_limit: usize) -> RankerAi<R> {
         RankerAi {
             recursion_limit: ::std::cmp::max(recursion_limit, 1),
-            ranker: ranker
+            ranker: ranker,
+            resign_below: None,
         }
     }
 }
@@ -39,26 +244,41 @@ impl ::std::ops::Neg for Score {
     }
 }
 
-impl <R: Ranker> Ai for RankerAi<R> {
-    fn choose(&mut self, state: &GameState, player: Player) -> HexPosition {
+impl <R: Ranker> RankerAi<R> {
+    fn search(&mut self, state: &GameState, player: Player, depth: usize) -> (Score, Option<HexPosition>) {
+        self.search_until(state, player, depth, None).unwrap()
+    }
+
+    // Like `search`, but gives up and returns `None` once `deadline` passes.
+    fn search_until(&mut self, state: &GameState, player: Player, depth: usize, deadline: Option<Instant>)
+                    -> Option<(Score, Option<HexPosition>)> {
         fn eval<R: Ranker>(
             rai: &mut RankerAi<R>,
             state: GameState,
             depth: i32,
             mut alpha: Score,
             beta: Score,
-            player: Player)
+            player: Player,
+            deadline: Option<Instant>,
+            out_of_time: &mut bool)
             -> (Score, Option<HexPosition>) {
+                if deadline.map(|d| Instant::now() >= d).unwrap_or(false) {
+                    *out_of_time = true;
+                    return (alpha, None);
+                }
                 if depth == 0 || state.map().is_full() || state.is_game_over() {
                     let score = Score(rai.ranker.rank(&state, player) - rai.ranker.rank(&state, player.inverse()), -depth);
                     return (score, None);
                 }
 
-                let available_moves = state.map().grid().iter().filter(|pos| !state.map().contains(pos));
+                let available_moves = state.legal_moves().into_iter();
                 let mut best = None;
 
                 for (state, mv) in available_moves.map(|mv| (state.with_move(&mv), mv)) {
-                    let (score, _) = eval(rai, state, depth - 1, -beta, -alpha, player.inverse());
+                    let (score, _) = eval(rai, state, depth - 1, -beta, -alpha, player.inverse(), deadline, out_of_time);
+                    if *out_of_time {
+                        return (alpha, best);
+                    }
                     let score = -score;
                     if score >= beta {
                         best = Some(mv);
@@ -74,13 +294,63 @@ impl <R: Ranker> Ai for RankerAi<R> {
                 return (alpha, best);
             }
 
-        let rec_lim = self.recursion_limit;
         let alpha = NEG_INFINITY;
         let beta = INFINITY;
-        let (r, p) = eval(self, state.clone(), rec_lim as i32, Score(alpha, 0), Score(beta, 0), player);
+        let mut out_of_time = false;
+        let (score, best) = eval(self, state.clone(), depth as i32, Score(alpha, 0), Score(beta, 0), player,
+                                 deadline, &mut out_of_time);
+        if out_of_time {
+            return None;
+        }
+        // When every move loses on the spot none of them beats the starting
+        // alpha, but there's still a move to play.
+        Some((score, best.or_else(|| state.legal_moves().into_iter().next())))
+    }
+}
 
+impl <R: Ranker> Ai for RankerAi<R> {
+    fn choose(&mut self, state: &GameState, player: Player) -> HexPosition {
+        let rec_lim = self.recursion_limit;
+        let (_, p) = self.search(state, player, rec_lim);
         p.unwrap()
     }
+
+    // Iterative deepening up to the recursion limit.  A search that runs
+    // past the budget is abandoned and the last finished one's move played;
+    // the first ply is always searched in full so there is a move.
+    fn choose_timed(&mut self, state: &GameState, player: Player, budget: Duration) -> HexPosition {
+        let deadline = Instant::now() + budget;
+        let mut best = self.search(state, player, 1).1.or_else(|| state.legal_moves().into_iter().next());
+        for depth in 2 .. self.recursion_limit + 1 {
+            match self.search_until(state, player, depth, Some(deadline)) {
+                Some((_, Some(p))) => best = Some(p),
+                Some((_, None)) => {}
+                None => break,
+            }
+        }
+        best.unwrap()
+    }
+
+    // Resigns below the threshold, and takes a draw when behind.
+    fn act(&mut self, state: &GameState, player: Player) -> Action {
+        let rec_lim = self.recursion_limit;
+        let (score, p) = self.search(state, player, rec_lim);
+        if self.resign_below.map(|threshold| score.0 <= threshold).unwrap_or(false) {
+            return Action::Resign;
+        }
+        if state.draw_offer() == Some(player.inverse()) && score.0 < 0.0 {
+            return Action::AcceptDraw;
+        }
+        Action::Place(p.unwrap())
+    }
+
+    fn score_moves(&mut self, state: &GameState, player: Player) -> Vec<(HexPosition, f32)> {
+        let depth = self.recursion_limit - 1;
+        state.legal_moves().into_iter().map(|mv| {
+            let (score, _) = self.search(&state.with_move(&mv), player.inverse(), depth);
+            (mv, -score.0)
+        }).collect()
+    }
 }
 
 pub struct NullRanker;
@@ -109,7 +379,77 @@ pub struct FeatureRanker {
     pub double_score: f32,
 }
 
+impl Default for FeatureRanker {
+    fn default() -> FeatureRanker {
+        FeatureRanker {
+            window_score: 1.7,
+            triad_score: -3.5,
+            slot_score: 1.2,
+            double_score: 2.2,
+        }
+    }
+}
+
 impl FeatureRanker {
+    /// Reads weights written as `name = value` lines, one per weight, such
+    /// as the output of this type's `Display` impl.  Missing weights keep
+    /// their default values.
+    pub fn parse(s: &str) -> Result<FeatureRanker, String> {
+        let mut ranker = FeatureRanker::default();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=').map(str::trim);
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => return Err(format!("bad weight line: {}", line)),
+            };
+            let value: f32 = value.parse().map_err(|e| format!("bad weight {}: {}", name, e))?;
+            match name {
+                "window_score" => ranker.window_score = value,
+                "triad_score" => ranker.triad_score = value,
+                "slot_score" => ranker.slot_score = value,
+                "double_score" => ranker.double_score = value,
+                _ => return Err(format!("unknown weight: {}", name)),
+            }
+        }
+        Ok(ranker)
+    }
+
+    pub fn load(path: &str) -> Result<FeatureRanker, String> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|e| format!("couldn't read {}: {}", path, e))?;
+        FeatureRanker::parse(&contents)
+    }
+
+    /// The weights in the order `window`, `triad`, `slot`, `double`, which
+    /// is also the order of `features`.
+    pub fn weights(&self) -> [f32; 4] {
+        [self.window_score, self.triad_score, self.slot_score, self.double_score]
+    }
+
+    pub fn from_weights(weights: [f32; 4]) -> FeatureRanker {
+        FeatureRanker {
+            window_score: weights[0],
+            triad_score: weights[1],
+            slot_score: weights[2],
+            double_score: weights[3],
+        }
+    }
+
+    /// The feature counts that the weights multiply, for `player`.
+    pub fn features(game: &GameState, player: Player) -> [f32; 4] {
+        [
+            FeatureRanker::count_windows(game, player) as f32,
+            FeatureRanker::count_triads(game, player) as f32,
+            FeatureRanker::count_slots(game, player) as f32,
+            FeatureRanker::count_doubles(game, player) as f32,
+        ]
+    }
+
     pub fn normalize(&mut self) {
         let total =
             self.window_score +
@@ -198,6 +538,15 @@ impl FeatureRanker {
     }
 }
 
+impl fmt::Display for FeatureRanker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "window_score = {}", self.window_score)?;
+        writeln!(f, "triad_score = {}", self.triad_score)?;
+        writeln!(f, "slot_score = {}", self.slot_score)?;
+        writeln!(f, "double_score = {}", self.double_score)
+    }
+}
+
 impl Ranker for FeatureRanker {
     fn rank(&mut self, state: &GameState, player: Player) -> f32 {
         match state.is_over() {
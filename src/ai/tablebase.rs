@@ -0,0 +1,86 @@
+use super::*;
+use solver::{Endgame, Value};
+
+/// Ranks positions the endgame covers by their exact value, and everything
+/// else with `inner`.  Proven wins rank above any estimate, and faster wins
+/// above slower ones, so a `RankerAi` whose search reaches the endgame
+/// plays the rest of the game perfectly.
+pub struct TablebaseRanker<R: Ranker> {
+    pub endgame: Endgame,
+    pub inner: R,
+}
+
+// Above anything the rankers estimate, but finite so that the distance to
+// the end still counts.
+const PROVEN: f32 = 1e6;
+
+impl <R: Ranker> TablebaseRanker<R> {
+    pub fn new(endgame: Endgame, inner: R) -> TablebaseRanker<R> {
+        TablebaseRanker {
+            endgame: endgame,
+            inner: inner,
+        }
+    }
+}
+
+impl <R: Ranker> Ranker for TablebaseRanker<R> {
+    fn rank(&mut self, state: &GameState, player: Player) -> f32 {
+        if state.is_over() != MoveResult::Good {
+            return self.inner.rank(state, player);
+        }
+        let value = match self.endgame.probe(state) {
+            Some(value) if state.current_player() == player => value,
+            Some(value) => value.back(),
+            None => return self.inner.rank(state, player),
+        };
+        match value {
+            Value::Win(n) => PROVEN - n as f32,
+            Value::Loss(n) => -PROVEN + n as f32,
+            Value::Tie => -100.0,
+        }
+    }
+}
+
+/// Plays the best move in positions the endgame covers, and otherwise
+/// leaves the choice to `inner`.
+pub struct TablebaseAi {
+    pub endgame: Endgame,
+    pub inner: Box<dyn Ai>,
+}
+
+impl TablebaseAi {
+    pub fn new(endgame: Endgame, inner: Box<dyn Ai>) -> TablebaseAi {
+        TablebaseAi {
+            endgame: endgame,
+            inner: inner,
+        }
+    }
+}
+
+impl Ai for TablebaseAi {
+    fn choose(&mut self, state: &GameState, player: Player) -> HexPosition {
+        match self.endgame.best_move(state) {
+            Some((mv, _)) => mv,
+            None => self.inner.choose(state, player),
+        }
+    }
+
+    fn choose_timed(&mut self, state: &GameState, player: Player, budget: Duration) -> HexPosition {
+        match self.endgame.best_move(state) {
+            Some((mv, _)) => mv,
+            None => self.inner.choose_timed(state, player, budget),
+        }
+    }
+
+    // Inside the endgame there's nothing to gain from resigning or a draw.
+    fn act(&mut self, state: &GameState, player: Player) -> Action {
+        match self.endgame.best_move(state) {
+            Some((mv, _)) => Action::Place(mv),
+            None => self.inner.act(state, player),
+        }
+    }
+
+    fn score_moves(&mut self, state: &GameState, player: Player) -> Vec<(HexPosition, f32)> {
+        self.inner.score_moves(state, player)
+    }
+}
@@ -0,0 +1,351 @@
+use super::*;
+use super::layer::{self, Layer};
+use rand::{self, Rng};
+use rand::distributions::{Gamma, IndependentSample};
+
+/// A policy and value network: one hidden layer of `tanh` units over the
+/// stones of the side to move and of their opponent, feeding a policy head
+/// with a logit per cell and a value head predicting the result for the
+/// side to move, from -1 for a loss to 1 for a win.
+///
+/// Trained by `training::fit_zero` on the searches of `ZeroAi`.
+#[derive(Clone)]
+pub struct ZeroNet {
+    radius: u32,
+    pub hidden_layer: Layer,
+    /// A unit per cell, in the order of `cells()`.
+    pub policy: Layer,
+    /// A single unit.
+    pub value: Layer,
+    cells: Vec<HexPosition>,
+    // Each cell's place in `cells`, by axial coordinates.
+    lookup: Vec<Option<usize>>,
+}
+
+/// The intermediate values of one forward pass, kept for training.
+pub struct ZeroActivations {
+    /// The inputs that are set; all others are zero.
+    pub active: Vec<usize>,
+    pub hidden: Vec<f32>,
+    pub policy: Vec<f32>,
+    pub value: f32,
+}
+
+impl ZeroNet {
+    /// A fresh network to start self-play from, for a board of `radius`
+    /// with `hidden` hidden units.
+    pub fn random<R: Rng>(radius: u32, hidden: usize, rng: &mut R) -> ZeroNet {
+        let mut net = ZeroNet::zeroed(radius, hidden);
+        net.hidden_layer = Layer::random(net.inputs(), hidden, rng);
+        net.policy = Layer::random(hidden, net.cells.len(), rng);
+        net.value = Layer::random(hidden, 1, rng);
+        net
+    }
+
+    fn zeroed(radius: u32, hidden: usize) -> ZeroNet {
+        let cells: Vec<_> = HexGrid::new(radius).iter().collect();
+        let n = cells.len();
+        let side = 2 * radius as usize + 1;
+        let mut lookup = vec![None; side * side];
+        for (i, cell) in cells.iter().enumerate() {
+            let (q, r) = cell.as_axial();
+            lookup[(r + radius as i32) as usize * side + (q + radius as i32) as usize] = Some(i);
+        }
+        ZeroNet {
+            radius: radius,
+            hidden_layer: Layer::new(2 * n, hidden),
+            policy: Layer::new(hidden, n),
+            value: Layer::new(hidden, 1),
+            cells: cells,
+            lookup: lookup,
+        }
+    }
+
+    pub fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    pub fn inputs(&self) -> usize {
+        2 * self.cells.len()
+    }
+
+    pub fn hidden(&self) -> usize {
+        self.hidden_layer.units()
+    }
+
+    /// The cells of the board, in the order of the policy head.
+    pub fn cells(&self) -> &[HexPosition] {
+        &self.cells
+    }
+
+    /// Where `pos` is in `cells()`.
+    pub fn index(&self, pos: &HexPosition) -> Option<usize> {
+        let radius = self.radius as i32;
+        let (q, r) = pos.as_axial();
+        if q.abs() > radius || r.abs() > radius {
+            return None;
+        }
+        let side = 2 * radius + 1;
+        self.lookup[((r + radius) * side + q + radius) as usize]
+    }
+
+    /// Reads a network saved by `Display`: a `zero` weight file as
+    /// described in `ai::layer`, holding the hidden layer, the policy head
+    /// and the value unit in that order.
+    pub fn parse(s: &str) -> Result<ZeroNet, String> {
+        let (radius, hidden, values) = layer::parse_weights(s, "zero")?;
+        let mut net = ZeroNet::zeroed(radius, hidden);
+        layer::read_layers(&mut [&mut net.hidden_layer, &mut net.policy, &mut net.value], &values)?;
+        Ok(net)
+    }
+
+    pub fn load(path: &str) -> Result<ZeroNet, String> {
+        ZeroNet::parse(&layer::read_file(path)?)
+    }
+
+    /// The inputs set for `state`, seen from the side to move.
+    pub fn encode(&self, state: &GameState) -> Vec<usize> {
+        let n = self.cells.len();
+        let player = state.current_player();
+        self.cells.iter().enumerate().filter_map(|(i, cell)| {
+            match state.map().get(cell) {
+                Some(&p) if p == player => Some(i),
+                Some(_) => Some(n + i),
+                None => None,
+            }
+        }).collect()
+    }
+
+    pub fn forward(&self, active: Vec<usize>) -> ZeroActivations {
+        let hidden: Vec<f32> = self.hidden_layer.sparse(&active).into_iter().map(f32::tanh).collect();
+        let policy = self.policy.dense(&hidden);
+        let value = self.value.dense(&hidden)[0].tanh();
+        ZeroActivations {
+            active: active,
+            hidden: hidden,
+            policy: policy,
+            value: value,
+        }
+    }
+
+    /// The network's move probabilities over the legal moves of `state`,
+    /// and its value for the side to move.
+    pub fn evaluate(&self, state: &GameState) -> (Vec<(HexPosition, f32)>, f32) {
+        let a = self.forward(self.encode(state));
+        (self.priors(state, &a.policy), a.value)
+    }
+
+    /// `evaluate` for many positions at once, a layer at a time.
+    pub fn evaluate_batch(&self, states: &[GameState]) -> Vec<(Vec<(HexPosition, f32)>, f32)> {
+        let actives: Vec<_> = states.iter().map(|state| self.encode(state)).collect();
+        let mut hidden = self.hidden_layer.sparse_batch(&actives);
+        for h in hidden.iter_mut().flat_map(|h| h.iter_mut()) {
+            *h = h.tanh();
+        }
+        let policies = self.policy.dense_batch(&hidden);
+        let values = self.value.dense_batch(&hidden);
+        states.iter().zip(policies).zip(values).map(|((state, policy), value)| {
+            (self.priors(state, &policy), value[0].tanh())
+        }).collect()
+    }
+
+    /// The softmax of `logits` over the legal moves of `state`.
+    pub fn priors(&self, state: &GameState, logits: &[f32]) -> Vec<(HexPosition, f32)> {
+        let moves: Vec<_> = state.legal_moves().into_iter()
+            .filter_map(|mv| self.index(&mv).map(|i| (mv, logits[i])))
+            .collect();
+        let max = moves.iter().map(|&(_, l)| l).fold(NEG_INFINITY, f32::max);
+        let total: f32 = moves.iter().map(|&(_, l)| (l - max).exp()).sum();
+        moves.into_iter().map(|(mv, l)| (mv, (l - max).exp() / total)).collect()
+    }
+}
+
+impl fmt::Display for ZeroNet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "zero {} {}", self.radius, self.hidden())?;
+        write!(f, "{}{}{}", self.hidden_layer, self.policy, self.value)
+    }
+}
+
+impl fmt::Debug for ZeroNet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ZeroNet({}, {})", self.radius, self.hidden())
+    }
+}
+
+/// Tree search guided by a `ZeroNet`, AlphaZero style: moves are explored
+/// by the PUCT rule, weighing the network's prior for each move against
+/// the values seen below it, and leaves are valued by the network instead
+/// of by random playouts.
+#[derive(Debug, Clone)]
+pub struct ZeroAi {
+    pub net: ZeroNet,
+    iterations: usize,
+    pub c_puct: f32,
+    /// Dirichlet noise mixed into the root's priors, as `(alpha, weight)`,
+    /// so that self-play tries moves the network doesn't yet like.
+    pub noise: Option<(f32, f32)>,
+}
+
+struct Node {
+    position: Option<HexPosition>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    expanded: bool,
+    prior: f32,
+    visits: u32,
+    // Summed from the point of view of the player who moved into the node.
+    value: f32,
+}
+
+impl Node {
+    fn new(position: Option<HexPosition>, parent: Option<usize>, prior: f32) -> Node {
+        Node {
+            position: position,
+            parent: parent,
+            children: vec![],
+            expanded: false,
+            prior: prior,
+            visits: 0,
+            value: 0.0,
+        }
+    }
+}
+
+// The value of a finished game for the side to move.
+fn terminal_value(state: &GameState) -> Option<f32> {
+    match state.is_over() {
+        MoveResult::Good => None,
+        result => Some(match result.winner() {
+            Some(p) if p == state.current_player() => 1.0,
+            Some(_) => -1.0,
+            None => 0.0,
+        }),
+    }
+}
+
+impl ZeroAi {
+    pub fn new(net: ZeroNet, iterations: usize) -> ZeroAi {
+        ZeroAi {
+            net: net,
+            iterations: ::std::cmp::max(iterations, 1),
+            c_puct: 1.5,
+            noise: None,
+        }
+    }
+
+    fn add_noise<R: Rng>(&self, tree: &mut [Node], rng: &mut R) {
+        let (alpha, weight) = match self.noise {
+            Some(noise) => noise,
+            None => return,
+        };
+        let gamma = Gamma::new(alpha as f64, 1.0);
+        let samples: Vec<_> = tree[0].children.iter().map(|_| gamma.ind_sample(rng) as f32).collect();
+        let total: f32 = samples.iter().sum();
+        if total <= 0.0 {
+            return;
+        }
+        for (&child, sample) in tree[0].children.clone().iter().zip(samples) {
+            tree[child].prior = (1.0 - weight) * tree[child].prior + weight * sample / total;
+        }
+    }
+
+    fn search<R: Rng>(&self, state: &GameState, limit: usize, deadline: Option<Instant>, rng: &mut R) -> Vec<Node> {
+        let mut tree = vec![Node::new(None, None, 1.0)];
+
+        for i in 0 .. limit {
+            if let Some(deadline) = deadline {
+                if i > 0 && Instant::now() >= deadline {
+                    break;
+                }
+            }
+
+            let mut node = 0;
+            let mut state = state.clone();
+
+            // selection
+            while tree[node].expanded && !tree[node].children.is_empty() {
+                let sqrt_visits = (tree[node].visits as f32).sqrt();
+                let c_puct = self.c_puct;
+                let puct = |n: &Node| {
+                    let q = if n.visits > 0 { n.value / n.visits as f32 } else { 0.0 };
+                    q + c_puct * n.prior * sqrt_visits / (1.0 + n.visits as f32)
+                };
+                let mut best = tree[node].children[0];
+                for &child in &tree[node].children {
+                    if puct(&tree[child]) > puct(&tree[best]) {
+                        best = child;
+                    }
+                }
+                node = best;
+                state.make_move(&tree[node].position.unwrap());
+            }
+
+            // evaluation and expansion
+            let value = match terminal_value(&state) {
+                Some(value) => value,
+                None => {
+                    let (priors, value) = self.net.evaluate(&state);
+                    for (mv, prior) in priors {
+                        tree.push(Node::new(Some(mv), Some(node), prior));
+                        let child = tree.len() - 1;
+                        tree[node].children.push(child);
+                    }
+                    tree[node].expanded = true;
+                    if node == 0 {
+                        self.add_noise(&mut tree, rng);
+                    }
+                    value
+                }
+            };
+
+            // backpropagation, flipping sides at every level
+            let mut value = -value;
+            let mut cur = Some(node);
+            while let Some(n) = cur {
+                tree[n].visits += 1;
+                tree[n].value += value;
+                value = -value;
+                cur = tree[n].parent;
+            }
+        }
+
+        tree
+    }
+
+    /// Searches `state` and returns each root move's share of the visits.
+    pub fn visit_distribution<R: Rng>(&self, state: &GameState, rng: &mut R) -> Vec<(HexPosition, f32)> {
+        let tree = self.search(state, self.iterations, None, rng);
+        ZeroAi::distribution(&tree)
+    }
+
+    fn distribution(tree: &[Node]) -> Vec<(HexPosition, f32)> {
+        let total = ::std::cmp::max(tree[0].visits, 1) as f32;
+        tree[0].children.iter()
+            .map(|&c| (tree[c].position.unwrap(), tree[c].visits as f32 / total))
+            .collect()
+    }
+
+    fn most_visited(tree: &[Node]) -> HexPosition {
+        let best = tree[0].children.iter().max_by_key(|&&c| tree[c].visits).unwrap();
+        tree[*best].position.unwrap()
+    }
+}
+
+impl Ai for ZeroAi {
+    fn choose(&mut self, state: &GameState, _player: Player) -> HexPosition {
+        let tree = self.search(state, self.iterations, None, &mut rand::thread_rng());
+        ZeroAi::most_visited(&tree)
+    }
+
+    fn choose_timed(&mut self, state: &GameState, _player: Player, budget: Duration) -> HexPosition {
+        let deadline = Some(Instant::now() + budget);
+        let tree = self.search(state, ::std::usize::MAX, deadline, &mut rand::thread_rng());
+        ZeroAi::most_visited(&tree)
+    }
+
+    /// Scores moves by their share of the root's visits.
+    fn score_moves(&mut self, state: &GameState, _player: Player) -> Vec<(HexPosition, f32)> {
+        self.visit_distribution(state, &mut rand::thread_rng())
+    }
+}
@@ -0,0 +1,84 @@
+//! Plain text rendering of the board for terminals and logs.
+//!
+//! ```text
+//!      . . . . .
+//!     . . . . . .
+//!    . . R G . . .
+//!   . . . . . . . .
+//!  . . . .[R]. . . .
+//!   . . . . . . . .
+//!    . . . . . . .
+//!     . . . . . .
+//!      . . . . .
+//! ```
+//!
+//! Red and Green stones are drawn as `R` and `G` and the last move is
+//! bracketed.  With coordinates enabled each row is prefixed by the name of
+//! its first cell in the cell notation, so the rest can be counted off from
+//! it:
+//!
+//! ```text
+//! a1      . . . . .
+//! b1     . . . . . .
+//! c1    . . R G . . .
+//! d1   . . . . . . . .
+//! e1  . . . .[R]. . . .
+//! f2   . . . . . . . .
+//! g3    . . . . . . .
+//! h4     . . . . . .
+//! i5      . . . . .
+//! ```
+
+use super::*;
+use std::fmt;
+
+pub fn render_ascii(state: &GameState, coordinates: bool) -> String {
+    let radius = state.config().radius as i32;
+    let mut out = String::new();
+
+    for r in -radius .. radius + 1 {
+        let row: Vec<_> = (-radius .. radius + 1)
+            .map(|q| HexPosition::from_axial(q, r))
+            .filter(|pos| state.map().could_contain(pos))
+            .collect();
+
+        if coordinates {
+            // Wide enough for the highest first cell, `1 + radius`.
+            let width = 1 + (1 + radius).to_string().len();
+            out.push_str(&format!("{:<1$} ", state.cell(row[0]).to_string(), width));
+        }
+        for _ in 0 .. r.abs() {
+            out.push(' ');
+        }
+
+        let mut prev_was_last = false;
+        for pos in &row {
+            let is_last = state.last_move() == Some(*pos);
+            out.push(if is_last { '[' } else if prev_was_last { ']' } else { ' ' });
+            out.push(match state.map().get(pos) {
+                Some(&Player::Red) => 'R',
+                Some(&Player::Green) => 'G',
+                None => '.',
+            });
+            prev_was_last = is_last;
+        }
+        if prev_was_last {
+            out.push(']');
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", render_ascii(self, false))
+    }
+}
+
+impl fmt::Debug for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GameState({})\n{}", self.to_notation(), render_ascii(self, true))
+    }
+}
@@ -0,0 +1,189 @@
+//! Game clocks.
+//!
+//! A `TimeControl` says how much time each player gets, and a `Clock` keeps
+//! track of it over a game.  Running out of time is the terminal result
+//! `MoveResult::Flagged`, which the clock reports when it's stopped, or at
+//! any time through `flagged`.
+
+use super::*;
+use hexagon::grid::Grid;
+use std::cmp;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TimeControl {
+    /// The whole game must be played within this time.
+    SuddenDeath(Duration),
+    /// A starting time, plus an increment after every move.
+    Fischer(Duration, Duration),
+    /// A fixed time for each move, which doesn't carry over.
+    PerMove(Duration),
+}
+
+fn seconds(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s.parse().map_err(|_| format!("bad time: {}", s))?;
+    if secs < 0.0 {
+        return Err(format!("bad time: {}", s));
+    }
+    Ok(Duration::from_millis((secs * 1000.0) as u64))
+}
+
+fn format_seconds(d: Duration) -> String {
+    let ms = d.as_secs() * 1000 + d.subsec_nanos() as u64 / 1_000_000;
+    if ms % 1000 == 0 {
+        format!("{}", ms / 1000)
+    } else {
+        format!("{}", ms as f64 / 1000.0)
+    }
+}
+
+/// Formats a duration as a clock face, `m:ss`, with tenths under ten
+/// seconds.
+pub fn format_time(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs < 10 {
+        format!("0:0{}.{}", secs, d.subsec_nanos() / 100_000_000)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+impl TimeControl {
+    /// Parses `sudden:SECS`, `fischer:SECS+INCREMENT` or `move:SECS`.
+    pub fn parse(spec: &str) -> Result<TimeControl, String> {
+        let mut parts = spec.splitn(2, ':');
+        let kind = parts.next().unwrap();
+        let rest = parts.next().ok_or(format!("bad time control: {}", spec))?;
+        match kind {
+            "sudden" => Ok(TimeControl::SuddenDeath(seconds(rest)?)),
+            "fischer" => {
+                let mut times = rest.splitn(2, '+');
+                let base = seconds(times.next().unwrap())?;
+                let increment = seconds(times.next().unwrap_or("0"))?;
+                Ok(TimeControl::Fischer(base, increment))
+            }
+            "move" => Ok(TimeControl::PerMove(seconds(rest)?)),
+            _ => Err(format!("unknown time control: {}", kind)),
+        }
+    }
+
+    /// What each player has on the clock before their first move.
+    pub fn initial(&self) -> Duration {
+        match *self {
+            TimeControl::SuddenDeath(time) => time,
+            TimeControl::Fischer(base, _) => base,
+            TimeControl::PerMove(time) => time,
+        }
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TimeControl::SuddenDeath(time) => write!(f, "sudden:{}", format_seconds(time)),
+            TimeControl::Fischer(base, increment) => {
+                write!(f, "fischer:{}+{}", format_seconds(base), format_seconds(increment))
+            }
+            TimeControl::PerMove(time) => write!(f, "move:{}", format_seconds(time)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Clock {
+    pub control: TimeControl,
+    remaining: [Duration; 2],
+    running: Option<(Player, Instant)>,
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::Red => 0,
+        Player::Green => 1,
+    }
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Clock {
+            control: control,
+            remaining: [control.initial(), control.initial()],
+            running: None,
+        }
+    }
+
+    /// The time `player` has left, counting down while their clock runs.
+    pub fn remaining(&self, player: Player) -> Duration {
+        let left = self.remaining[index(player)];
+        match self.running {
+            Some((p, since)) if p == player => left - cmp::min(left, since.elapsed()),
+            _ => left,
+        }
+    }
+
+    pub fn running(&self) -> Option<Player> {
+        self.running.map(|(p, _)| p)
+    }
+
+    /// The player whose time has run out, if any.
+    pub fn flagged(&self) -> Option<Player> {
+        [Player::Red, Player::Green].iter().cloned()
+            .find(|&p| self.remaining(p) == Duration::from_millis(0))
+    }
+
+    /// Starts `player`'s clock, stopping the other one.
+    pub fn start(&mut self, player: Player) {
+        self.stop();
+        self.running = Some((player, Instant::now()));
+    }
+
+    /// Stops the running clock at the end of a move, adding any increment.
+    /// Returns `MoveResult::Flagged` if the move took too long.
+    pub fn stop(&mut self) -> Option<MoveResult> {
+        let (player, since) = match self.running.take() {
+            Some(running) => running,
+            None => return None,
+        };
+        let i = index(player);
+        let elapsed = since.elapsed();
+        if elapsed >= self.remaining[i] {
+            self.remaining[i] = Duration::from_millis(0);
+            return Some(MoveResult::Flagged(player));
+        }
+
+        self.remaining[i] = match self.control {
+            TimeControl::SuddenDeath(_) => self.remaining[i] - elapsed,
+            TimeControl::Fischer(_, increment) => self.remaining[i] - elapsed + increment,
+            TimeControl::PerMove(time) => time,
+        };
+        None
+    }
+
+    /// How long `player` should think about their move in `state`: their
+    /// remaining time spread over the moves they can still expect to make,
+    /// plus any increment.
+    pub fn budget(&self, state: &GameState, player: Player) -> Duration {
+        let left = self.remaining(player);
+        match self.control {
+            // Leave a little for getting the move back to the clock.
+            TimeControl::PerMove(_) => left / 5 * 4,
+            TimeControl::SuddenDeath(_) | TimeControl::Fischer(..) => {
+                let open = state.map().grid().iter().filter(|pos| !state.map().contains(pos)).count() as u32;
+                let mut budget = left / cmp::max((open + 1) / 2, 1);
+                if let TimeControl::Fischer(_, increment) = self.control {
+                    budget = cmp::min(budget + increment, left);
+                }
+                budget
+            }
+        }
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "red {}  green {}",
+               format_time(self.remaining(Player::Red)),
+               format_time(self.remaining(Player::Green)))
+    }
+}
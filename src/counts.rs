@@ -0,0 +1,100 @@
+//! Counting every continuation of a position, ply by ply, as perft does
+//! for chess.
+//!
+//! The counts depend on nothing but the rules, so they pin down move
+//! generation and end-of-game detection.  A new board representation can be
+//! checked against the current one by comparing its counts with the ones in
+//! `tests/perft.rs`.
+//!
+//! Games that end are not followed any further.  Wins and losses are from
+//! the point of view of the player who made the last move: a win is four or
+//! more in a row, a loss three.
+
+use super::*;
+use std::fmt;
+
+/// The positions reached after some number of plies.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Counts {
+    pub nodes: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub ties: u64,
+}
+
+impl Counts {
+    pub fn new(nodes: u64, wins: u64, losses: u64, ties: u64) -> Counts {
+        Counts {
+            nodes: nodes,
+            wins: wins,
+            losses: losses,
+            ties: ties,
+        }
+    }
+
+    /// Counts a move by `mover` that had `result`.
+    fn record(&mut self, mover: Player, result: MoveResult) {
+        self.nodes += 1;
+        match result {
+            MoveResult::Good => {}
+            MoveResult::End(p) if p == mover => self.wins += 1,
+            MoveResult::End(_) => self.losses += 1,
+            MoveResult::Tie => self.ties += 1,
+            other => panic!("a legal move gave {:?}", other),
+        }
+    }
+
+    pub fn add(&mut self, other: &Counts) {
+        self.nodes += other.nodes;
+        self.wins += other.wins;
+        self.losses += other.losses;
+        self.ties += other.ties;
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} nodes, {} wins, {} losses, {} ties", self.nodes, self.wins, self.losses, self.ties)
+    }
+}
+
+fn count(state: &GameState, counts: &mut [Counts]) {
+    if let Some((here, deeper)) = counts.split_first_mut() {
+        for mv in state.legal_moves() {
+            let mut next = state.clone();
+            let result = next.make_move(&mv);
+            here.record(state.current_player(), result);
+            if result == MoveResult::Good {
+                count(&next, deeper);
+            }
+        }
+    }
+}
+
+/// The counts after each of 1 to `depth` plies from `state`.
+pub fn perft(state: &GameState, depth: usize) -> Vec<Counts> {
+    let mut counts = vec![Counts::default(); depth];
+    if state.is_over() == MoveResult::Good {
+        count(state, &mut counts);
+    }
+    counts
+}
+
+/// The counts after `depth` plies split by the first move, for narrowing
+/// down where two move generators disagree.
+pub fn divide(state: &GameState, depth: usize) -> Vec<(HexPosition, Counts)> {
+    if depth == 0 || state.is_over() != MoveResult::Good {
+        return vec![];
+    }
+    state.legal_moves().into_iter().map(|mv| {
+        let mut next = state.clone();
+        let result = next.make_move(&mv);
+        let mut counts = Counts::default();
+        if depth == 1 {
+            counts.record(state.current_player(), result);
+        } else if result == MoveResult::Good {
+            counts = perft(&next, depth - 1)[depth - 2];
+        }
+        (mv, counts)
+    }).collect()
+}
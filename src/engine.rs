@@ -0,0 +1,45 @@
+extern crate hexgame;
+
+use hexgame::ai::from_spec;
+use hexgame::protocol::Engine;
+
+use std::io::{self, BufRead, Write};
+
+const USAGE: &'static str = "\
+usage: engine [AI]
+
+Speaks the text protocol documented in `hexgame::protocol` on stdin and
+stdout.  AI is one of ranker:DEPTH[:WEIGHTS_FILE],
+linear:DEPTH[:WEIGHTS_FILE], mlp:DEPTH:WEIGHTS_FILE, null:DEPTH,
+mcts:ITERATIONS or zero:ITERATIONS:WEIGHTS_FILE, any of which may be
+wrapped as tablebase:FILE:AI, and defaults to ranker:4.";
+
+fn main() {
+    let spec = ::std::env::args().nth(1).unwrap_or("ranker:4".to_string());
+    let ai = match from_spec(&spec) {
+        Ok(ai) => ai,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            ::std::process::exit(1);
+        }
+    };
+
+    let mut engine = Engine::new(ai);
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        if let Some(response) = engine.handle_line(&line) {
+            stdout.write_all(response.as_bytes()).unwrap();
+            stdout.flush().unwrap();
+        }
+        if engine.is_quit() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,104 @@
+extern crate hexgame;
+
+use hexgame::GameState;
+use hexgame::image::{to_svg, to_png, to_animated_svg};
+use hexgame::record::GameRecord;
+
+use std::fs::File;
+use std::io::{BufReader, Write};
+
+const USAGE: &'static str = "\
+usage: export [--game N] [--animate | --frames] INPUT OUTPUT
+
+INPUT is a record file, or a position string such as
+\"5/6/7/8/4r4/8/7/6/5 g 4 e5\".  Records are exported at their final
+position unless one of these is given:
+
+    --animate   write the whole game as one animated svg
+    --frames    write every position of the game, numbering the files
+                by inserting the move number before OUTPUT's extension
+    --game N    use the Nth record in the file (starting at 1)
+
+OUTPUT ending in .png is written as a png, anything else as svg.";
+
+enum Mode {
+    Final,
+    Animate,
+    Frames,
+}
+
+fn write_state(state: &GameState, path: &str) -> Result<(), String> {
+    let bytes = if path.ends_with(".png") {
+        to_png(state)
+    } else {
+        to_svg(state).into_bytes()
+    };
+    File::create(path)
+        .and_then(|mut f| f.write_all(&bytes))
+        .map_err(|e| format!("couldn't write {}: {}", path, e))
+}
+
+fn frame_path(path: &str, i: usize) -> String {
+    match path.rfind('.') {
+        Some(dot) => format!("{}_{:03}{}", &path[.. dot], i, &path[dot ..]),
+        None => format!("{}_{:03}", path, i),
+    }
+}
+
+fn load_record(path: &str, game: usize) -> Result<GameRecord, String> {
+    let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+    match GameRecord::read(BufReader::new(file)).nth(game - 1) {
+        Some(Ok(record)) => Ok(record),
+        Some(Err(e)) => Err(e.to_string()),
+        None => Err(format!("{} has no game {}", path, game)),
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut mode = Mode::Final;
+    let mut game = 1;
+    let mut paths = vec![];
+
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--animate" => mode = Mode::Animate,
+            "--frames" => mode = Mode::Frames,
+            "--game" => {
+                game = args.next().and_then(|n| n.parse().ok()).ok_or("bad --game".to_string())?;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 || game == 0 {
+        return Err("expected INPUT and OUTPUT".to_string());
+    }
+    let (input, output) = (&paths[0], &paths[1]);
+
+    if let Ok(state) = GameState::from_notation(input) {
+        return write_state(&state, output);
+    }
+
+    let record = load_record(input, game)?;
+    match mode {
+        Mode::Final => write_state(&record.final_state(), output),
+        Mode::Frames => {
+            for (i, state) in record.states().iter().enumerate() {
+                write_state(state, &frame_path(output, i))?;
+            }
+            Ok(())
+        }
+        Mode::Animate => {
+            File::create(output)
+                .and_then(|mut f| f.write_all(to_animated_svg(&record, 0.5).as_bytes()))
+                .map_err(|e| format!("couldn't write {}: {}", output, e))
+        }
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        println!("{}\n{}", e, USAGE);
+        ::std::process::exit(1);
+    }
+}
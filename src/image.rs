@@ -0,0 +1,236 @@
+//! Headless rendering of positions and games to SVG and PNG.
+//!
+//! The board is drawn the same way as in `play` and `vis`, using the hex
+//! geometry from `hexagon::screen::ScreenSpace`.
+
+use super::*;
+use hexagon::grid::Grid;
+use hexagon::screen::ScreenSpace;
+use record::GameRecord;
+use std::f32::{INFINITY, NEG_INFINITY};
+use std::fmt::Write;
+
+const TILE_SIZE: f32 = 50.0;
+const HEX_SIZE: f32 = 45.0;
+const MARGIN: f32 = 10.0;
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const EMPTY: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+struct Hex {
+    position: HexPosition,
+    points: Vec<(f32, f32)>,
+    color: [f32; 4],
+}
+
+struct Canvas {
+    width: f32,
+    height: f32,
+    hexes: Vec<Hex>,
+}
+
+fn last_move_color(c: [f32; 4]) -> [f32; 4] {
+    [c[0] * 0.6, c[1] * 0.6, c[2] * 0.6, c[3]]
+}
+
+fn canvas(state: &GameState) -> Canvas {
+    let screen = ScreenSpace {
+        size: TILE_SIZE,
+        origin: (0.0, 0.0),
+    };
+    let hex = |position: HexPosition, color: [f32; 4], size: f32| Hex {
+        position: position,
+        points: screen.points_on_tile_custom_size(&position, size).iter().cloned().collect(),
+        color: color,
+    };
+
+    let mut hexes: Vec<_> = state.map().grid().iter().map(|pos| {
+        let color = state.map().get(&pos).map(Player::color).unwrap_or(EMPTY);
+        hex(pos, color, HEX_SIZE)
+    }).collect();
+    if let Some(last) = state.last_move() {
+        let color = last_move_color(state.map().get(&last).unwrap().color());
+        hexes.push(hex(last, color, HEX_SIZE * 0.6));
+    }
+
+    // Shift everything so the board's bounding box starts at the margin.
+    let points = || hexes.iter().flat_map(|h| h.points.iter());
+    let min_x = points().fold(INFINITY, |m, p| m.min(p.0));
+    let min_y = points().fold(INFINITY, |m, p| m.min(p.1));
+    let max_x = points().fold(NEG_INFINITY, |m, p| m.max(p.0));
+    let max_y = points().fold(NEG_INFINITY, |m, p| m.max(p.1));
+    for hex in &mut hexes {
+        for p in &mut hex.points {
+            *p = (p.0 - min_x + MARGIN, p.1 - min_y + MARGIN);
+        }
+    }
+
+    Canvas {
+        width: (max_x - min_x + 2.0 * MARGIN).ceil(),
+        height: (max_y - min_y + 2.0 * MARGIN).ceil(),
+        hexes: hexes,
+    }
+}
+
+fn svg_color(c: [f32; 4]) -> String {
+    let byte = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(c[0]), byte(c[1]), byte(c[2]))
+}
+
+fn svg_points(points: &[(f32, f32)]) -> String {
+    let points: Vec<_> = points.iter().map(|&(x, y)| format!("{:.1},{:.1}", x, y)).collect();
+    points.join(" ")
+}
+
+fn svg_header(out: &mut String, canvas: &Canvas) {
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+             w = canvas.width, h = canvas.height).unwrap();
+    writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", svg_color(BACKGROUND)).unwrap();
+}
+
+pub fn to_svg(state: &GameState) -> String {
+    let canvas = canvas(state);
+    let mut out = String::new();
+    svg_header(&mut out, &canvas);
+    for hex in &canvas.hexes {
+        writeln!(out, "<polygon points=\"{}\" fill=\"{}\"/>", svg_points(&hex.points), svg_color(hex.color)).unwrap();
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// A single SVG that plays through the whole game, `seconds_per_move` apart.
+pub fn to_animated_svg(record: &GameRecord, seconds_per_move: f32) -> String {
+    let states = record.states();
+    let canvas = canvas(&GameState::with_config(record.config));
+    let mut out = String::new();
+    svg_header(&mut out, &canvas);
+
+    for hex in &canvas.hexes {
+        writeln!(out, "<polygon points=\"{}\" fill=\"{}\">", svg_points(&hex.points), svg_color(hex.color)).unwrap();
+        for (i, (mv, state)) in record.moves.iter().zip(&states[1 ..]).enumerate() {
+            if mv.position == hex.position {
+                let color = state.map().get(&mv.position).map(Player::color).unwrap_or(EMPTY);
+                writeln!(out, "  <set attributeName=\"fill\" to=\"{}\" begin=\"{:.2}s\" fill=\"freeze\"/>",
+                         svg_color(color), (i + 1) as f32 * seconds_per_move).unwrap();
+            }
+        }
+        out.push_str("</polygon>\n");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn inside(points: &[(f32, f32)], x: f32, y: f32) -> bool {
+    let mut sign = 0.0;
+    for i in 0 .. points.len() {
+        let (ax, ay) = points[i];
+        let (bx, by) = points[(i + 1) % points.len()];
+        let cross = (bx - ax) * (y - ay) - (by - ay) * (x - ax);
+        if cross != 0.0 {
+            if sign * cross < 0.0 {
+                return false;
+            }
+            sign = cross;
+        }
+    }
+    true
+}
+
+/// Rasterises the position and encodes it as a PNG.
+pub fn to_png(state: &GameState) -> Vec<u8> {
+    let canvas = canvas(state);
+    let (width, height) = (canvas.width as usize, canvas.height as usize);
+    let byte = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+
+    let mut pixels = vec![0u8; width * height * 3];
+    for y in 0 .. height {
+        for x in 0 .. width {
+            let i = (y * width + x) * 3;
+            pixels[i] = byte(BACKGROUND[0]);
+            pixels[i + 1] = byte(BACKGROUND[1]);
+            pixels[i + 2] = byte(BACKGROUND[2]);
+        }
+    }
+
+    for hex in &canvas.hexes {
+        let min_x = hex.points.iter().fold(INFINITY, |m, p| m.min(p.0)).max(0.0) as usize;
+        let min_y = hex.points.iter().fold(INFINITY, |m, p| m.min(p.1)).max(0.0) as usize;
+        let max_x = hex.points.iter().fold(0.0f32, |m, p| m.max(p.0)).ceil() as usize;
+        let max_y = hex.points.iter().fold(0.0f32, |m, p| m.max(p.1)).ceil() as usize;
+        for y in min_y .. ::std::cmp::min(max_y, height) {
+            for x in min_x .. ::std::cmp::min(max_x, width) {
+                if inside(&hex.points, x as f32 + 0.5, y as f32 + 0.5) {
+                    let i = (y * width + x) * 3;
+                    pixels[i] = byte(hex.color[0]);
+                    pixels[i + 1] = byte(hex.color[1]);
+                    pixels[i + 2] = byte(hex.color[2]);
+                }
+            }
+        }
+    }
+
+    encode_png(width as u32, height as u32, &pixels)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0 .. 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &d in data {
+        a = (a + d as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn push_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&[(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]);
+}
+
+fn push_chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    push_u32(out, data.len() as u32);
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start ..]);
+    push_u32(out, crc);
+}
+
+// 8-bit RGB, with the image data in uncompressed deflate blocks.
+fn encode_png(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(rgb.len() + height as usize);
+    for row in rgb.chunks(width as usize * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<_> = raw.chunks(0xffff).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        let len = block.len() as u16;
+        zlib.push(if i + 1 == blocks.len() { 1 } else { 0 });
+        zlib.extend_from_slice(&[len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]);
+        zlib.extend_from_slice(block);
+    }
+    push_u32(&mut zlib, adler32(&raw));
+
+    let mut ihdr = vec![];
+    push_u32(&mut ihdr, width);
+    push_u32(&mut ihdr, height);
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut out = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    push_chunk(&mut out, b"IHDR", &ihdr);
+    push_chunk(&mut out, b"IDAT", &zlib);
+    push_chunk(&mut out, b"IEND", &[]);
+    out
+}
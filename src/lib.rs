@@ -2,15 +2,43 @@ extern crate hexagon;
 extern crate rand;
 
 pub mod ai;
+pub mod ascii;
+pub mod clock;
+pub mod counts;
+pub mod image;
+pub mod net;
+pub mod notation;
+pub mod protocol;
+pub mod puzzle;
+pub mod record;
+pub mod solver;
+pub mod suite;
+pub mod symmetry;
+pub mod training;
 
-use hexagon::grid::{Map, HexGrid};
+use hexagon::grid::{Map, HexGrid, Grid};
 use hexagon::HexPosition;
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct GameState {
+    config: Config,
     current_player: Player,
     map: Map<Player, HexGrid>,
     last_move: Option<HexPosition>,
+    // Set when the game ended off the board, by resignation or agreement.
+    resolution: Option<MoveResult>,
+    draw_offer: Option<Player>,
+}
+
+/// The largest board radius accepted from engines and position strings.
+pub const MAX_RADIUS: u32 = 12;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Config {
+    pub radius: u32,
+    /// The pie rule: on their first turn Green may take over Red's opening
+    /// stone instead of placing one, by playing on it.
+    pub swap: bool,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
@@ -25,14 +53,52 @@ pub enum MoveResult {
     Bad,
     End(Player),
     Tie,
+    /// The player ran out of time.  Only clocks produce this; the board
+    /// itself never does.
+    Flagged(Player),
+    /// The player resigned.
+    Resigned(Player),
+    /// The players agreed to a draw.
+    Draw,
+}
+
+/// Everything a player can do on their turn.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Action {
+    Place(HexPosition),
+    Resign,
+    /// Offer a draw, which stands until the opponent's next action.  The
+    /// player offering still has to make their move.
+    OfferDraw,
+    AcceptDraw,
+    /// Take over Red's first stone under the swap rule.
+    Swap,
+}
+
+impl MoveResult {
+    /// The player who won, if the game has ended with a winner.
+    pub fn winner(&self) -> Option<Player> {
+        match *self {
+            MoveResult::End(p) => Some(p),
+            MoveResult::Flagged(p) | MoveResult::Resigned(p) => Some(p.inverse()),
+            _ => None,
+        }
+    }
 }
 
 impl GameState {
     pub fn new() -> GameState {
+        GameState::with_config(Config::standard())
+    }
+
+    pub fn with_config(config: Config) -> GameState {
         GameState {
-            current_player: Player::Red,
-            map: Map::new(HexGrid::new(4)),
+            config: config,
+            current_player: Player::starting(),
+            map: Map::new(HexGrid::new(config.radius)),
             last_move: None,
+            resolution: None,
+            draw_offer: None,
         }
     }
 
@@ -43,32 +109,111 @@ impl GameState {
     }
 
     pub fn make_move(&mut self, pos: &HexPosition) -> MoveResult {
-        if self.map.could_contain(&pos) && !self.map.contains(&pos) {
-            self.map.insert(pos, self.current_player);
-            self.current_player = self.current_player.inverse();
-            self.last_move = Some(*pos);
-            self.is_over()
+        if self.resolution.is_some() || !self.is_legal(pos) {
+            return MoveResult::Bad;
+        }
+        // Moving turns down the opponent's draw offer.
+        if self.draw_offer != Some(self.current_player) {
+            self.draw_offer = None;
+        }
+
+        let swap = self.is_swap(pos);
+        self.map.insert(pos, self.current_player);
+        self.current_player = self.current_player.inverse();
+        self.last_move = Some(*pos);
+        if swap {
+            MoveResult::Good
         } else {
-            MoveResult::Bad
+            self.is_over()
         }
     }
 
+    /// Carries out `action` for the current player.  Placing a stone is the
+    /// same as `make_move`.  Nothing can be done once the game is over.
+    pub fn act(&mut self, action: Action) -> MoveResult {
+        if self.is_over() != MoveResult::Good {
+            return MoveResult::Bad;
+        }
+        match action {
+            Action::Place(pos) => self.make_move(&pos),
+            Action::Swap => match self.swap_available() {
+                Some(pos) => self.make_move(&pos),
+                None => MoveResult::Bad,
+            },
+            Action::Resign => {
+                self.resolution = Some(MoveResult::Resigned(self.current_player));
+                self.is_over()
+            }
+            Action::OfferDraw if self.draw_offer.is_none() => {
+                self.draw_offer = Some(self.current_player);
+                MoveResult::Good
+            }
+            Action::AcceptDraw if self.draw_offer == Some(self.current_player.inverse()) => {
+                self.resolution = Some(MoveResult::Draw);
+                self.is_over()
+            }
+            Action::OfferDraw | Action::AcceptDraw => MoveResult::Bad,
+        }
+    }
+
+    pub fn with_action(&self, action: Action) -> GameState {
+        let mut c = self.clone();
+        c.act(action);
+        c
+    }
+
+    /// The player whose draw offer is waiting for an answer.
+    pub fn draw_offer(&self) -> Option<Player> {
+        self.draw_offer
+    }
+
+    /// The stone Green may take over under the swap rule, if they're
+    /// allowed to right now.
+    pub fn swap_available(&self) -> Option<HexPosition> {
+        if !self.config.swap || self.current_player != Player::Green {
+            return None;
+        }
+        let mut stones = self.map.iter();
+        match (stones.next(), stones.next()) {
+            (Some((pos, &Player::Red)), None) => Some(pos),
+            _ => None,
+        }
+    }
+
+    pub fn is_swap(&self, pos: &HexPosition) -> bool {
+        self.swap_available() == Some(*pos)
+    }
+
+    pub fn is_legal(&self, pos: &HexPosition) -> bool {
+        (self.map.could_contain(pos) && !self.map.contains(pos)) || self.is_swap(pos)
+    }
+
+    /// Every move the current player can make: the empty cells, and the
+    /// swap if it's available.
+    pub fn legal_moves(&self) -> Vec<HexPosition> {
+        let mut moves: Vec<_> = self.map.grid().iter().filter(|pos| !self.map.contains(pos)).collect();
+        moves.extend(self.swap_available());
+        moves
+    }
+
     pub fn is_game_over(&self) -> bool {
         match self.is_over() {
-            MoveResult::End(_) => true,
+            MoveResult::End(_) | MoveResult::Resigned(_) | MoveResult::Draw => true,
             _ => false
         }
     }
 
     pub fn is_over(&self) -> MoveResult {
-        if self.map.is_full() {
-            return MoveResult::Tie;
+        if let Some(resolution) = self.resolution {
+            return resolution;
         }
 
-        let last_move = if let Some(last_move) = self.last_move {
-            last_move
-        } else {
-            return MoveResult::Good;
+        // A line made by the last stone counts even when it fills the
+        // board; only then is a full board a tie.
+        let last_move = match self.last_move {
+            Some(last_move) => last_move,
+            None if self.map.is_full() => return MoveResult::Tie,
+            None => return MoveResult::Good,
         };
 
         let player = self.map.get(&last_move).unwrap();
@@ -86,13 +231,21 @@ impl GameState {
             };
         }
 
-        if let Some(who_won) = status {
-            MoveResult::End(who_won)
-        } else {
-            MoveResult::Good
+        match status {
+            Some(who_won) => MoveResult::End(who_won),
+            None if self.map.is_full() => MoveResult::Tie,
+            None => MoveResult::Good,
         }
     }
 
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    pub fn last_move(&self) -> Option<HexPosition> {
+        self.last_move
+    }
+
     pub fn current_player(&self) -> Player {
         self.current_player
     }
@@ -102,6 +255,21 @@ impl GameState {
     }
 }
 
+impl Config {
+    pub fn standard() -> Config {
+        Config {
+            radius: 4,
+            swap: false,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::standard()
+    }
+}
+
 impl Player {
     pub fn starting() -> Player {
         Player::Red
@@ -0,0 +1,182 @@
+//! The line protocol spoken between the game server and its clients.
+//!
+//! Clients send:
+//!
+//! * `play <name>` - join the next game that needs a player
+//! * `host <red> <green>` - start a game in which the client moves for both
+//!   sides, for broadcasting games played elsewhere
+//! * `watch [game]` - spectate `game`, or without one follow whichever game
+//!   started most recently
+//! * `move <cell>`
+//! * `resign` - in a hosted game, for the side to move
+//!
+//! The server sends:
+//!
+//! * `welcome <game> <colour>` - you are playing `colour` in game `game`
+//! * `watching <game> <red> <green>` - you are now spectating `game`; the
+//!   game so far follows as a `position` and a `moved` for each move
+//! * `position <position>` - the starting position, as a position string
+//! * `moved <colour> <cell>` - a move was played in your game
+//! * `over <result> <reason>` - the game ended; result is `Red`, `Green`,
+//!   `Tie`, or `*` if it was abandoned
+//! * `error <text>` - your last message was rejected
+//!
+//! Names are single words.  Cells use the notation from `notation`.
+
+use super::*;
+use protocol::{colour_name, parse_colour};
+use record::{result_from_str, result_to_str};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::thread;
+
+pub const DEFAULT_ADDRESS: &'static str = "127.0.0.1:7700";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    Play(String),
+    Host(String, String),
+    Watch(Option<usize>),
+    Move(String),
+    Resign,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    Welcome(usize, Player),
+    Watching(usize, String, String),
+    Position(String),
+    Moved(Player, String),
+    Over(Option<MoveResult>, String),
+    Error(String),
+}
+
+fn split(line: &str) -> (&str, &str) {
+    let line = line.trim();
+    match line.find(' ') {
+        Some(i) => (&line[.. i], line[i ..].trim()),
+        None => (line, ""),
+    }
+}
+
+impl ClientMessage {
+    pub fn parse(line: &str) -> Option<ClientMessage> {
+        match split(line) {
+            ("play", name) => Some(ClientMessage::Play(name.to_string())),
+            ("host", names) => match split(names) {
+                (red, green) if !red.is_empty() && !green.is_empty() => {
+                    Some(ClientMessage::Host(red.to_string(), green.to_string()))
+                }
+                _ => None,
+            },
+            ("watch", "") => Some(ClientMessage::Watch(None)),
+            ("watch", game) => game.parse().ok().map(|game| ClientMessage::Watch(Some(game))),
+            ("move", cell) if !cell.is_empty() => Some(ClientMessage::Move(cell.to_string())),
+            ("resign", "") => Some(ClientMessage::Resign),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ClientMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClientMessage::Play(ref name) => write!(f, "play {}", name),
+            ClientMessage::Host(ref red, ref green) => write!(f, "host {} {}", red, green),
+            ClientMessage::Watch(None) => write!(f, "watch"),
+            ClientMessage::Watch(Some(game)) => write!(f, "watch {}", game),
+            ClientMessage::Move(ref cell) => write!(f, "move {}", cell),
+            ClientMessage::Resign => write!(f, "resign"),
+        }
+    }
+}
+
+impl ServerMessage {
+    pub fn parse(line: &str) -> Option<ServerMessage> {
+        let (command, rest) = split(line);
+        let (first, second) = split(rest);
+        match command {
+            "welcome" => match (first.parse(), parse_colour(second)) {
+                (Ok(game), Some(colour)) => Some(ServerMessage::Welcome(game, colour)),
+                _ => None,
+            },
+            "watching" => {
+                let (red, green) = split(second);
+                match first.parse() {
+                    Ok(game) => Some(ServerMessage::Watching(game, red.to_string(), green.to_string())),
+                    _ => None,
+                }
+            }
+            "position" => Some(ServerMessage::Position(rest.to_string())),
+            "moved" => parse_colour(first).map(|c| ServerMessage::Moved(c, second.to_string())),
+            "over" => result_from_str(first).map(|r| ServerMessage::Over(r, second.to_string())),
+            "error" => Some(ServerMessage::Error(rest.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ServerMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ServerMessage::Welcome(game, colour) => write!(f, "welcome {} {}", game, colour_name(colour)),
+            ServerMessage::Watching(game, ref red, ref green) => write!(f, "watching {} {} {}", game, red, green),
+            ServerMessage::Position(ref position) => write!(f, "position {}", position),
+            ServerMessage::Moved(colour, ref cell) => write!(f, "moved {} {}", colour_name(colour), cell),
+            ServerMessage::Over(result, ref reason) => write!(f, "over {} {}", result_to_str(result), reason),
+            ServerMessage::Error(ref text) => write!(f, "error {}", text),
+        }
+    }
+}
+
+/// Turns a free-form name into one word, as the protocol requires.
+pub fn single_word(name: &str) -> String {
+    let word: String = name.split_whitespace().collect::<Vec<_>>().join("_");
+    if word.is_empty() { "anonymous".to_string() } else { word }
+}
+
+/// Publishes games played locally to a server, so that spectators can
+/// watch them live.  Failures are reported once and then ignored; the
+/// games themselves should carry on whether anyone is watching or not.
+pub struct Broadcaster {
+    stream: Option<TcpStream>,
+}
+
+impl Broadcaster {
+    pub fn connect(address: &str) -> io::Result<Broadcaster> {
+        let stream = TcpStream::connect(address)?;
+
+        // Nobody reads the server's replies, but they still have to be
+        // drained so that the server never blocks writing to us.
+        let reader = BufReader::new(stream.try_clone()?);
+        thread::spawn(move || for _ in reader.lines() {});
+
+        Ok(Broadcaster { stream: Some(stream) })
+    }
+
+    fn send(&mut self, message: ClientMessage) {
+        let failed = match self.stream {
+            Some(ref mut stream) => writeln!(stream, "{}", message).is_err(),
+            None => false,
+        };
+        if failed {
+            println!("lost connection to the server, no longer broadcasting");
+            self.stream = None;
+        }
+    }
+
+    /// Starts a new game.  The previous one must have finished.
+    pub fn start(&mut self, red: &str, green: &str) {
+        self.send(ClientMessage::Host(single_word(red), single_word(green)));
+    }
+
+    pub fn play(&mut self, state: &GameState, pos: HexPosition) {
+        self.send(ClientMessage::Move(state.move_name(pos)));
+    }
+
+    /// Resigns the game for the side to move.
+    pub fn resign(&mut self) {
+        self.send(ClientMessage::Resign);
+    }
+}
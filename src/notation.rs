@@ -0,0 +1,284 @@
+//! Textual notation for cells and positions.
+//!
+//! Cells are named by a row letter and a number, as in the usual Yavalath
+//! coordinates.  Row `a` is the top row of the board (`r == -radius`).  In
+//! the top half and the middle row cells are numbered from 1 at the left;
+//! below the middle the numbers carry on along the diagonals, so each row
+//! starts one higher than the last.  The standard board runs `a1`-`a5`,
+//! `e1`-`e9` and `i5`-`i9`, with the centre at `e5`.
+//!
+//! Positions are written FEN-style as four space separated fields:
+//!
+//! ```text
+//! 5/6/2rg3/8/4r4/8/7/6/5 g 4 e5
+//! ```
+//!
+//! The board rows from top to bottom (`r` and `g` for stones, digits for runs
+//! of empty cells), the side to move, the board radius (at most
+//! `MAX_RADIUS`) and the last move played (`-` if there is none).  The
+//! radius is followed by `s` when the swap rule is in force, as in `4s`.
+//! Resignations and draw offers are not part of a position.
+//!
+//! A swap is written `swap` in move lists, but as a move it is just the cell
+//! being taken over, so either is accepted.
+
+use super::*;
+use std::cmp::{max, min};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct Cell {
+    pub position: HexPosition,
+    pub radius: u32,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NotationError {
+    BadCell(String),
+    BadPosition(String),
+}
+
+fn row_bounds(r: i32, radius: i32) -> (i32, i32) {
+    (max(-radius, -r - radius), min(radius, -r + radius))
+}
+
+// The number of the first cell in row `r`.
+fn row_start(r: i32) -> i32 {
+    1 + max(r, 0)
+}
+
+impl Cell {
+    pub fn new(position: HexPosition, config: Config) -> Cell {
+        Cell {
+            position: position,
+            radius: config.radius,
+        }
+    }
+
+    /// Parses a cell in the notation described above.  Plain axial `q,r`
+    /// coordinates are accepted as well.
+    pub fn parse(s: &str, config: Config) -> Result<Cell, NotationError> {
+        let s = s.trim();
+        let err = || NotationError::BadCell(s.to_string());
+        let radius = config.radius as i32;
+
+        if s.contains(',') {
+            let mut parts = s.split(',');
+            let q = parts.next().and_then(|q| q.trim().parse().ok());
+            let r = parts.next().and_then(|r| r.trim().parse().ok());
+            return match (q, r, parts.next()) {
+                (Some(q), Some(r), None) => Ok(Cell::new(HexPosition::from_axial(q, r), config)),
+                _ => Err(err()),
+            };
+        }
+
+        let mut chars = s.chars();
+        let row = match chars.next() {
+            Some(c) if c.is_ascii_alphabetic() => c.to_ascii_lowercase() as i32 - 'a' as i32,
+            _ => return Err(err()),
+        };
+        let index: i32 = chars.as_str().parse().map_err(|_| err())?;
+
+        let r = row - radius;
+        if r > radius {
+            return Err(err());
+        }
+        let (q_min, q_max) = row_bounds(r, radius);
+        if index < row_start(r) || index - row_start(r) > q_max - q_min {
+            return Err(err());
+        }
+        let q = q_min + index - row_start(r);
+
+        Ok(Cell::new(HexPosition::from_axial(q, r), config))
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let radius = self.radius as i32;
+        let (q, r) = self.position.as_axial();
+        let (q_min, q_max) = row_bounds(r, radius);
+        if r.abs() > radius || q < q_min || q > q_max || r + radius >= 26 {
+            return write!(f, "{},{}", q, r);
+        }
+        let row = (b'a' + (r + radius) as u8) as char;
+        write!(f, "{}{}", row, q - q_min + row_start(r))
+    }
+}
+
+impl fmt::Debug for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cell({})", self)
+    }
+}
+
+impl FromStr for Cell {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Cell, NotationError> {
+        Cell::parse(s, Config::standard())
+    }
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NotationError::BadCell(ref s) => write!(f, "bad cell: {}", s),
+            NotationError::BadPosition(ref s) => write!(f, "bad position: {}", s),
+        }
+    }
+}
+
+impl Player {
+    pub fn to_char(&self) -> char {
+        match *self {
+            Player::Red => 'r',
+            Player::Green => 'g',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Player> {
+        match c.to_ascii_lowercase() {
+            'r' => Some(Player::Red),
+            'g' => Some(Player::Green),
+            _ => None,
+        }
+    }
+}
+
+impl GameState {
+    pub fn cell(&self, position: HexPosition) -> Cell {
+        Cell::new(position, self.config)
+    }
+
+    pub fn parse_cell(&self, s: &str) -> Result<HexPosition, NotationError> {
+        Cell::parse(s, self.config).map(|c| c.position)
+    }
+
+    /// Like `parse_cell`, but also accepts `swap` when a swap is available.
+    pub fn parse_move(&self, s: &str) -> Result<HexPosition, NotationError> {
+        match (s.trim(), self.swap_available()) {
+            ("swap", Some(pos)) => Ok(pos),
+            _ => self.parse_cell(s),
+        }
+    }
+
+    /// The name of a move: its cell, or `swap`.
+    pub fn move_name(&self, position: HexPosition) -> String {
+        if self.is_swap(&position) {
+            "swap".to_string()
+        } else {
+            self.cell(position).to_string()
+        }
+    }
+
+    pub fn to_notation(&self) -> String {
+        let radius = self.config.radius as i32;
+        let mut rows = vec![];
+        for r in -radius .. radius + 1 {
+            let (q_min, q_max) = row_bounds(r, radius);
+            let mut row = String::new();
+            let mut empty = 0;
+            for q in q_min .. q_max + 1 {
+                match self.map.get(&HexPosition::from_axial(q, r)) {
+                    Some(p) => {
+                        if empty > 0 {
+                            row.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        row.push(p.to_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                row.push_str(&empty.to_string());
+            }
+            rows.push(row);
+        }
+
+        let last = match self.last_move {
+            Some(pos) => self.cell(pos).to_string(),
+            None => "-".to_string(),
+        };
+
+        let swap = if self.config.swap { "s" } else { "" };
+        format!("{} {} {}{} {}", rows.join("/"), self.current_player.to_char(), self.config.radius, swap, last)
+    }
+
+    pub fn from_notation(s: &str) -> Result<GameState, NotationError> {
+        let err = || NotationError::BadPosition(s.to_string());
+        let fields: Vec<_> = s.split_whitespace().collect();
+        if fields.len() != 4 {
+            return Err(err());
+        }
+
+        let swap = fields[2].ends_with('s');
+        let size = if swap { &fields[2][.. fields[2].len() - 1] } else { fields[2] };
+        let config = Config {
+            radius: size.parse().map_err(|_| err())?,
+            swap: swap,
+        };
+        if config.radius < 1 || config.radius > MAX_RADIUS {
+            return Err(err());
+        }
+        let mut state = GameState::with_config(config);
+        let radius = config.radius as i32;
+
+        let mut chars = fields[1].chars();
+        state.current_player = match (chars.next().and_then(Player::from_char), chars.next()) {
+            (Some(p), None) => p,
+            _ => return Err(err()),
+        };
+
+        let rows: Vec<_> = fields[0].split('/').collect();
+        if rows.len() as i32 != 2 * radius + 1 {
+            return Err(err());
+        }
+        for (row, r) in rows.iter().zip(-radius ..) {
+            let (q_min, q_max) = row_bounds(r, radius);
+            let mut q = q_min;
+            let mut digits = String::new();
+            for c in row.chars().chain(Some(' ')) {
+                if c.is_ascii_digit() {
+                    digits.push(c);
+                    continue;
+                }
+                if !digits.is_empty() {
+                    let run = digits.parse::<i32>().map_err(|_| err())?;
+                    if run > q_max + 1 - q {
+                        return Err(err());
+                    }
+                    q += run;
+                    digits.clear();
+                }
+                if c == ' ' {
+                    break;
+                }
+                let player = Player::from_char(c).ok_or_else(err)?;
+                if q > q_max {
+                    return Err(err());
+                }
+                state.map.insert(&HexPosition::from_axial(q, r), player);
+                q += 1;
+            }
+            if q != q_max + 1 {
+                return Err(err());
+            }
+        }
+
+        state.last_move = match fields[3] {
+            "-" => None,
+            cell => {
+                let pos = state.parse_cell(cell).map_err(|_| err())?;
+                if !state.map.contains(&pos) {
+                    return Err(err());
+                }
+                Some(pos)
+            }
+        };
+
+        Ok(state)
+    }
+}
@@ -0,0 +1,70 @@
+extern crate hexgame;
+
+use hexgame::{Config, GameState};
+use hexgame::counts::{divide, perft};
+
+use std::time::Instant;
+
+const USAGE: &'static str = "\
+usage: perft [--radius R] [--swap] [--divide] DEPTH [POSITION]
+
+Counts the positions reached after each of 1 to DEPTH plies from POSITION,
+written as in `hexgame::notation`, or from the empty board of radius R
+(default 4).  Wins and losses are those of the player who just moved; games
+that end aren't followed further.
+
+    --swap      play the empty board with the swap rule
+    --divide    split the count at DEPTH by the first move";
+
+fn parse<T: ::std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    value.and_then(|v| v.parse().ok()).ok_or(format!("bad value for {}", flag))
+}
+
+fn run() -> Result<(), String> {
+    let mut config = Config::standard();
+    let mut split = false;
+    let mut depth = None;
+    let mut position = None;
+
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--radius" => config.radius = parse(&arg, args.next())?,
+            "--swap" => config.swap = true,
+            "--divide" => split = true,
+            _ if depth.is_none() => depth = Some(parse("DEPTH", Some(arg))?),
+            _ if position.is_none() => position = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    let depth: usize = depth.ok_or("missing DEPTH".to_string())?;
+    let state = match position {
+        Some(position) => GameState::from_notation(&position).map_err(|e| e.to_string())?,
+        None => GameState::with_config(config),
+    };
+
+    let start = Instant::now();
+    if split {
+        let moves = divide(&state, depth);
+        let mut total = 0;
+        for &(mv, ref counts) in &moves {
+            println!("{:5} {}", state.move_name(mv), counts.nodes);
+            total += counts.nodes;
+        }
+        println!("{} moves, {} nodes", moves.len(), total);
+    } else {
+        for (i, counts) in perft(&state, depth).iter().enumerate() {
+            println!("{:2} {}", i + 1, counts);
+        }
+    }
+    let elapsed = start.elapsed();
+    println!("{}.{:03}s", elapsed.as_secs(), elapsed.subsec_nanos() / 1_000_000);
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        println!("{}\n{}", e, USAGE);
+        ::std::process::exit(1);
+    }
+}
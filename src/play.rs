@@ -2,8 +2,11 @@ extern crate hexagon;
 extern crate lux;
 extern crate hexgame;
 
-use hexgame::{GameState, Player, MoveResult};
+use hexgame::{Action, Config, GameState, Player, MoveResult};
 use hexgame::ai::*;
+use hexgame::clock::{Clock, TimeControl};
+use hexgame::net::{ClientMessage, ServerMessage};
+use hexgame::record;
 use lux::prelude::*;
 use lux::interactive::Event;
 use lux::graphics::{ColorVertex, PrimitiveType, PrimitiveCanvas};
@@ -11,7 +14,14 @@ use hexagon::*;
 use hexagon::screen::ScreenSpace;
 use hexagon::grid::Grid;
 
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread::spawn;
+use std::time::Duration;
+
 const TIME_BETWEEN_GAMES: u32 = 3_000;
+const TIME_BETWEEN_AI_MOVES: u32 = 500;
 
 fn draw_hex(frame: &mut Frame, screen: &ScreenSpace, hex: &HexPosition, color: [f32; 4], size: f32) {
     fn get_color_vertex((x, y): (f32, f32), color: [f32; 4]) -> ColorVertex {
@@ -33,6 +43,12 @@ fn draw_hex(frame: &mut Frame, screen: &ScreenSpace, hex: &HexPosition, color: [
     ], None, None).unwrap();
 }
 
+fn draw_heatmap(frame: &mut Frame, screen: &ScreenSpace, heatmap: &[(HexPosition, [f32; 4])]) {
+    for &(tile, color) in heatmap {
+        draw_hex(frame, screen, &tile, color, 45.0);
+    }
+}
+
 fn render_game(frame: &mut Frame, state: &GameState, screen: &ScreenSpace) {
     let size = 45.0;
 
@@ -45,20 +61,190 @@ fn render_game(frame: &mut Frame, state: &GameState, screen: &ScreenSpace) {
     }
 }
 
+const USAGE: &'static str = "\
+usage: play [--heatmap SPEC] [--clock CONTROL] [--swap] [RED] [GREEN]
+       play [--heatmap SPEC] --connect ADDRESS [SEAT]
+
+Each seat is either `human` or an ai:
+    ranker:DEPTH[:WEIGHTS_FILE]
+    linear:DEPTH[:WEIGHTS_FILE]
+    mlp:DEPTH:WEIGHTS_FILE
+    null:DEPTH
+    mcts:ITERATIONS
+    zero:ITERATIONS:WEIGHTS_FILE
+    tablebase:FILE:SPEC
+
+The default is `play human ranker:4`.
+
+--connect joins a game on a `server` at ADDRESS, playing SEAT (`human`
+by default) against whoever the server pairs us with.
+
+Press `r` to resign, and `d` to offer a draw or accept one.  Draws can't
+be offered over the network.
+
+--swap plays with the swap rule: on their first turn Green may click on
+Red's first stone to take it over.
+
+--clock plays with a game clock, shown as a bar for each player in the
+top left corner.  CONTROL is one of:
+    sudden:SECS              the whole game in SECS
+    fischer:SECS+INCREMENT   SECS, plus INCREMENT after each move
+    move:SECS                SECS for each move
+
+--heatmap colours the empty cells by the opinion of an ai given as above,
+or by `status` to show winning, losing and must-block cells.";
+
+enum Seat {
+    Human,
+    Computer(Box<dyn Ai>),
+    Remote,
+}
+
+fn parse_seat(spec: &str) -> Result<Seat, String> {
+    match spec {
+        "human" => Ok(Seat::Human),
+        spec => from_spec(spec).map(Seat::Computer),
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    messages: Receiver<ServerMessage>,
+    waiting: bool,
+}
+
+impl Connection {
+    fn send(&mut self, message: ClientMessage) {
+        if writeln!(self.stream, "{}", message).is_err() {
+            println!("lost connection to the server");
+        }
+    }
+}
+
+// Joins a game and waits to be told which colour we are playing.
+fn connect(address: &str, name: &str) -> Result<(Connection, Player), String> {
+    let mut stream = TcpStream::connect(address).map_err(|e| format!("couldn't connect to {}: {}", address, e))?;
+    writeln!(stream, "{}", ClientMessage::Play(name.to_string())).map_err(|e| e.to_string())?;
+
+    let mut lines = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?).lines();
+    let colour = loop {
+        match lines.next() {
+            Some(Ok(line)) => match ServerMessage::parse(&line) {
+                Some(ServerMessage::Welcome(game, colour)) => {
+                    println!("joined game {} as {:?}", game, colour);
+                    break colour;
+                }
+                Some(ServerMessage::Error(e)) => return Err(e),
+                _ => {}
+            },
+            _ => return Err("the server hung up".to_string()),
+        }
+    };
+
+    let (s, r) = channel();
+    spawn(move || {
+        for line in lines {
+            let message = match line {
+                Ok(line) => ServerMessage::parse(&line),
+                Err(_) => break,
+            };
+            if let Some(message) = message {
+                if s.send(message).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = s.send(ServerMessage::Over(None, "connection lost".to_string()));
+    });
+
+    Ok((Connection {
+        stream: stream,
+        messages: r,
+        waiting: false,
+    }, colour))
+}
+
 fn main() {
+    let mut args = vec![];
+    let mut heatmap_source = None;
+    let mut address = None;
+    let mut time_control = None;
+    let mut config = Config::standard();
+    let mut argv = ::std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        if arg == "--heatmap" {
+            match argv.next().ok_or("missing heatmap".to_string()).and_then(|s| HeatmapSource::from_spec(&s)) {
+                Ok(source) => heatmap_source = Some(source),
+                Err(e) => {
+                    println!("{}\n{}", e, USAGE);
+                    return;
+                }
+            }
+        } else if arg == "--clock" {
+            match argv.next().ok_or("missing time control".to_string()).and_then(|s| TimeControl::parse(&s)) {
+                Ok(control) => time_control = Some(control),
+                Err(e) => {
+                    println!("{}\n{}", e, USAGE);
+                    return;
+                }
+            }
+        } else if arg == "--swap" {
+            config.swap = true;
+        } else if arg == "--connect" {
+            address = argv.next();
+        } else {
+            args.push(arg);
+        }
+    }
+    if args.len() > 2 || (address.is_some() && (args.len() > 1 || time_control.is_some() || config.swap)) {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let mut connection = None;
+    let mut seats = match address {
+        Some(address) => {
+            let spec = args.get(0).map(|s| &s[..]).unwrap_or("human");
+            let result = parse_seat(spec).and_then(|seat| connect(&address, spec).map(|c| (seat, c)));
+            match result {
+                Ok((seat, (conn, Player::Red))) => {
+                    connection = Some(conn);
+                    [seat, Seat::Remote]
+                }
+                Ok((seat, (conn, Player::Green))) => {
+                    connection = Some(conn);
+                    [Seat::Remote, seat]
+                }
+                Err(e) => {
+                    println!("{}\n{}", e, USAGE);
+                    return;
+                }
+            }
+        }
+        None => {
+            let red = args.get(0).map(|s| &s[..]).unwrap_or("human");
+            let green = args.get(1).map(|s| &s[..]).unwrap_or("ranker:4");
+            match (parse_seat(red), parse_seat(green)) {
+                (Ok(red), Ok(green)) => [red, green],
+                (Err(e), _) | (_, Err(e)) => {
+                    println!("{}\n{}", e, USAGE);
+                    return;
+                }
+            }
+        }
+    };
+    let watching = seats.iter().all(|seat| match *seat { Seat::Computer(_) => true, _ => false });
+
     let mut window = match Window::new_with_defaults() {
         Ok(window) => window,
         Err(e) => panic!("oh fuck: {}", e),
     };
 
-    let mut game = GameState::new();
-
-    let mut ai = RankerAi::new(FeatureRanker {
-        window_score: 1.7,
-        triad_score: -3.5,
-        slot_score: 1.2,
-        double_score: 2.2,
-    }, 4);
+    let mut game = GameState::with_config(config);
+    let mut heatmap: Option<(GameState, Vec<_>)> = None;
+    let mut remote_result = None;
+    let mut clock = time_control.map(Clock::new);
+    let mut last_clock = String::new();
 
     let screenspace = ScreenSpace {
         size: 50.0,
@@ -68,43 +254,210 @@ fn main() {
     while window.is_open() {
         //screenspace.origin = (window.width() / 2.0, window.height() / 2.0);
 
+        if let Some(ref mut conn) = connection {
+            while let Ok(message) = conn.messages.try_recv() {
+                match message {
+                    ServerMessage::Position(position) => match GameState::from_notation(&position) {
+                        Ok(state) => game = state,
+                        Err(e) => println!("{}", e),
+                    },
+                    ServerMessage::Moved(_, cell) => {
+                        match game.parse_move(&cell) {
+                            Ok(pos) => { game.make_move(&pos); }
+                            Err(e) => println!("{}", e),
+                        }
+                        conn.waiting = false;
+                    }
+                    ServerMessage::Over(result, reason) => {
+                        println!("game over: {} ({})", record::result_to_str(result), reason);
+                        remote_result = Some(result);
+                    }
+                    ServerMessage::Error(e) => {
+                        println!("server: {}", e);
+                        conn.waiting = false;
+                    }
+                    ServerMessage::Welcome(..) | ServerMessage::Watching(..) => {}
+                }
+            }
+        }
+
         let mut frame = window.cleared_frame((0.0, 0.0, 0.0));
         let (x, y) = window.mouse_pos();
+        let events: Vec<Event> = window.events().collect();
+        let clicked = events.iter().any(|e| match e { &Event::MouseUp(_) => true, _ => false });
+        let key = |k| events.iter().any(|e| match e { &Event::KeyPressed(_, Some(c), _) => c == k, _ => false });
+        let (resign_key, draw_key) = (key('r'), key('d'));
 
         render_game(&mut frame, &game, &screenspace);
-        if let MoveResult::End(p) = game.is_over() {
-            display_gameover(frame, p);
+        if let Some(ref mut source) = heatmap_source {
+            if heatmap.as_ref().map(|&(ref state, _)| *state != game).unwrap_or(true) {
+                heatmap = Some((game.clone(), source.heatmap(&game)));
+            }
+            draw_heatmap(&mut frame, &screenspace, &heatmap.as_ref().unwrap().1);
+        }
+
+        if let Some(ref clock) = clock {
+            draw_clocks(&mut frame, clock);
+            let text = clock.to_string();
+            if text != last_clock {
+                println!("{}", text);
+                last_clock = text;
+            }
+        }
+
+        if let Some(p) = clock.as_ref().and_then(|c| c.flagged()) {
+            println!("{:?} ran out of time", p);
+            display_gameover(frame, p.inverse().color());
             ::std::thread::sleep_ms(TIME_BETWEEN_GAMES);
-            game = GameState::new();
+            game = GameState::with_config(config);
+            clock = time_control.map(Clock::new);
             continue;
         }
 
-        let near_cursor = &screenspace.nearest_hex(x, y);
-        if game.map().could_contain(&near_cursor) && !game.map().contains(&near_cursor) {
-            draw_hex(&mut frame, &screenspace, near_cursor, game.current_player().color(), 45.0);
-            draw_hex(&mut frame, &screenspace, near_cursor, [1.0, 1.0, 1.0, 1.0], 40.0);
+        if let Some(result) = remote_result {
+            let color = match result.and_then(|r| r.winner()) {
+                Some(p) => p.color(),
+                None => [1.0, 1.0, 1.0, 1.0],
+            };
+            display_gameover(frame, color);
+            ::std::thread::sleep_ms(TIME_BETWEEN_GAMES);
+            return;
+        }
+        match game.is_over() {
+            MoveResult::Good => {}
+            // Over the network the server tells us when the game ends.
+            _ if connection.is_some() => continue,
+            result => {
+                match result {
+                    MoveResult::Resigned(p) => println!("{:?} resigned", p),
+                    MoveResult::Draw => println!("drawn by agreement"),
+                    _ => {}
+                }
+                let color = match result.winner() {
+                    Some(p) => p.color(),
+                    None => [1.0, 1.0, 1.0, 1.0],
+                };
+                display_gameover(frame, color);
+                ::std::thread::sleep_ms(TIME_BETWEEN_GAMES);
+                game = GameState::with_config(config);
+                clock = time_control.map(Clock::new);
+                continue;
+            }
+        }
+
+        if let Some(ref mut conn) = connection {
+            if resign_key {
+                conn.send(ClientMessage::Resign);
+            }
+            if conn.waiting {
+                continue;
+            }
         }
 
-        if window.events().filter(|e| match e { &Event::MouseUp(_) => true, _ => false}).count() != 0 {
-            if game.map().could_contain(&near_cursor) {
-                if let MoveResult::End(_) = game.make_move(near_cursor) {
-                    continue;
+        if let Some(ref mut clock) = clock {
+            if clock.running() != Some(game.current_player()) {
+                clock.start(game.current_player());
+            }
+        }
+
+        let seat = match game.current_player() {
+            Player::Red => &mut seats[0],
+            Player::Green => &mut seats[1],
+        };
+
+        let next = match *seat {
+            Seat::Human => {
+                let near_cursor = screenspace.nearest_hex(x, y);
+                if game.is_legal(&near_cursor) {
+                    draw_hex(&mut frame, &screenspace, &near_cursor, game.current_player().color(), 45.0);
+                    draw_hex(&mut frame, &screenspace, &near_cursor, [1.0, 1.0, 1.0, 1.0], 40.0);
                 }
 
-                let next = ai.choose(&game, game.current_player());
-                game.make_move(&next);
+                if clicked && game.is_legal(&near_cursor) {
+                    Some(Action::Place(near_cursor))
+                } else if resign_key && connection.is_none() {
+                    Some(Action::Resign)
+                } else if draw_key && connection.is_none() {
+                    match game.draw_offer() {
+                        Some(p) if p != game.current_player() => Some(Action::AcceptDraw),
+                        Some(_) => None,
+                        None => {
+                            println!("{:?} offers a draw", game.current_player());
+                            Some(Action::OfferDraw)
+                        }
+                    }
+                } else {
+                    None
+                }
+            }
+            Seat::Computer(ref mut ai) => {
+                // Present the board before the ai starts thinking.
+                drop(frame);
+                let next = match clock {
+                    Some(ref clock) => Action::Place(ai.choose_clocked(&game, game.current_player(), clock)),
+                    None => ai.act(&game, game.current_player()),
+                };
+                window.events().count();
+                if watching {
+                    ::std::thread::sleep_ms(TIME_BETWEEN_AI_MOVES);
+                }
+                Some(next)
+            }
+            Seat::Remote => None,
+        };
+
+        if let Some(next) = next {
+            match (&mut connection, next) {
+                (&mut Some(ref mut conn), Action::Place(pos)) => {
+                    conn.send(ClientMessage::Move(game.move_name(pos)));
+                    conn.waiting = true;
+                }
+                (&mut Some(ref mut conn), Action::Resign) => conn.send(ClientMessage::Resign),
+                // The server knows nothing of draws.
+                (&mut Some(_), _) => {}
+                (&mut None, Action::OfferDraw) => {
+                    game.act(next);
+                }
+                (&mut None, _) => {
+                    // A flag that fell while the move was being made is
+                    // caught by `flagged` on the next frame.
+                    if clock.as_mut().and_then(|c| c.stop()).is_none() {
+                        game.act(next);
+                    }
+                }
             }
         }
     }
 }
 
-fn display_gameover(mut frame: Frame, player: Player) {
+// A bar for each player's remaining time, as a fraction of their time at
+// the start.  The clock that's running is drawn brighter.
+fn draw_clocks(frame: &mut Frame, clock: &Clock) {
+    let initial = clock.control.initial();
+    let millis = |d: Duration| (d.as_secs() * 1000 + d.subsec_nanos() as u64 / 1_000_000) as f32;
+
+    for (i, &player) in [Player::Red, Player::Green].iter().enumerate() {
+        let fraction = (millis(clock.remaining(player)) / millis(initial).max(1.0)).min(1.0);
+        let c = player.color();
+        let shade = if clock.running() == Some(player) { 1.0 } else { 0.5 };
+        frame.draw(Rectangle {
+            x: 60.0,
+            y: 10.0 + 20.0 * i as f32,
+            w: 200.0 * fraction,
+            h: 15.0,
+            color: [c[0] * shade, c[1] * shade, c[2] * shade, c[3]],
+            .. Default::default()
+        }).unwrap();
+    }
+}
+
+fn display_gameover(mut frame: Frame, color: [f32; 4]) {
     frame.draw(Rectangle {
         x: 0.0,
         y: 0.0,
         w: 50.0,
         h: 50.0,
-        color: player.color(),
+        color: color,
         .. Default::default()
     }).unwrap();
 }
@@ -0,0 +1,414 @@
+//! A GTP-style line protocol for driving an `Ai`.
+//!
+//! Each command is one line, optionally prefixed by a numeric id.  Every
+//! response starts with `=` on success or `?` on failure, followed by the
+//! id if one was given, the response text, and a blank line:
+//!
+//! ```text
+//! 1 genmove red
+//! =1 e5
+//!
+//! play green e5
+//! ? illegal move
+//!
+//! ```
+//!
+//! Colours are `red`/`r` and `green`/`g`.  For the benefit of generic GTP
+//! tools `black`/`b` is accepted for Red, who moves first, and `white`/`w`
+//! for Green.  Cells use the notation from `notation`.
+//!
+//! Commands:
+//!
+//! * `protocol_version`, `name`, `version`, `list_commands`,
+//!   `known_command <command>`, `quit`
+//! * `boardsize <radius>` - start a new game on a board of that radius
+//! * `swap_rule <on|off>` - start a new game with or without the swap rule
+//! * `clear_board` - start a new game
+//! * `play <colour> <cell>`
+//! * `genmove <colour>` - the engine picks, plays and prints a move, or
+//!   `resign` if it gives up the game
+//! * `undo` - take back the last move
+//! * `showboard` - the board as text, with the position string on top
+//! * `time_settings <main> <byo_yomi> <stones>` - seconds of main time,
+//!   then `byo_yomi` seconds for every `stones` moves; all zero means no
+//!   limit
+//! * `time_left <colour> <seconds> <stones>`
+//! * `final_score` - `R+`, `G+`, `R+Resign`, `G+Resign`, `0` for a tie or
+//!   draw, or `?` if not over
+
+use super::*;
+use ai::Ai;
+use ascii::render_ascii;
+use hexagon::grid::Grid;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const COMMANDS: &'static [&'static str] = &[
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "quit",
+    "boardsize",
+    "swap_rule",
+    "clear_board",
+    "play",
+    "genmove",
+    "undo",
+    "showboard",
+    "time_settings",
+    "time_left",
+    "final_score",
+];
+
+#[derive(Debug, Copy, Clone)]
+pub struct TimeSettings {
+    pub main: Duration,
+    pub byo_yomi: Duration,
+    pub stones: u32,
+}
+
+pub struct Engine {
+    ai: Box<dyn Ai>,
+    history: Vec<GameState>,
+    time: Option<TimeSettings>,
+    time_left: [Option<Duration>; 2],
+    quit: bool,
+}
+
+pub fn parse_colour(s: &str) -> Option<Player> {
+    match &s.to_lowercase()[..] {
+        "red" | "r" | "black" | "b" => Some(Player::Red),
+        "green" | "g" | "white" | "w" => Some(Player::Green),
+        _ => None,
+    }
+}
+
+pub fn colour_name(player: Player) -> &'static str {
+    match player {
+        Player::Red => "red",
+        Player::Green => "green",
+    }
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::Red => 0,
+        Player::Green => 1,
+    }
+}
+
+fn seconds(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s.parse().map_err(|_| format!("bad time: {}", s))?;
+    if secs < 0.0 {
+        return Err(format!("bad time: {}", s));
+    }
+    Ok(Duration::from_millis((secs * 1000.0) as u64))
+}
+
+impl Engine {
+    pub fn new(ai: Box<dyn Ai>) -> Engine {
+        Engine {
+            ai: ai,
+            history: vec![GameState::new()],
+            time: None,
+            time_left: [None, None],
+            quit: false,
+        }
+    }
+
+    pub fn state(&self) -> &GameState {
+        self.history.last().unwrap()
+    }
+
+    /// Set once a `quit` command has been answered.
+    pub fn is_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Handles one line of input, returning the full response to send back.
+    /// Blank lines and `#` comments get no response.
+    pub fn handle_line(&mut self, line: &str) -> Option<String> {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            return None;
+        }
+
+        let mut words = line.split_whitespace().peekable();
+        let id = match words.peek().and_then(|w| w.parse::<u32>().ok()) {
+            Some(id) => {
+                words.next();
+                id.to_string()
+            }
+            None => String::new(),
+        };
+        let command = words.next().unwrap_or("");
+        let args: Vec<_> = words.collect();
+
+        Some(match self.execute(command, &args) {
+            Ok(text) => format!("={} {}\n\n", id, text),
+            Err(text) => format!("?{} {}\n\n", id, text),
+        })
+    }
+
+    pub fn execute(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        let arg = |i: usize| args.get(i).cloned().ok_or(format!("{} needs more arguments", command));
+        let colour = |i: usize| arg(i).and_then(|c| parse_colour(c).ok_or(format!("bad colour: {}", c)));
+
+        match command {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok("hexgame".to_string()),
+            "version" => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            "known_command" => Ok(COMMANDS.contains(&arg(0)?).to_string()),
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            "boardsize" => {
+                let radius = arg(0)?.parse().map_err(|_| "bad board size".to_string())?;
+                if radius < 1 || radius > MAX_RADIUS {
+                    return Err("unacceptable size".to_string());
+                }
+                let config = Config {
+                    radius: radius,
+                    .. self.state().config()
+                };
+                self.history = vec![GameState::with_config(config)];
+                Ok(String::new())
+            }
+            "swap_rule" => {
+                let swap = match arg(0)? {
+                    "on" => true,
+                    "off" => false,
+                    other => return Err(format!("expected on or off, not {}", other)),
+                };
+                let config = Config {
+                    swap: swap,
+                    .. self.state().config()
+                };
+                self.history = vec![GameState::with_config(config)];
+                Ok(String::new())
+            }
+            "clear_board" => {
+                let config = self.state().config();
+                self.history = vec![GameState::with_config(config)];
+                self.time_left = [None, None];
+                Ok(String::new())
+            }
+            "play" => {
+                let player = colour(0)?;
+                let state = self.state().clone();
+                let pos = state.parse_move(arg(1)?).map_err(|e| e.to_string())?;
+                self.play(player, pos).map(|_| String::new())
+            }
+            "genmove" => {
+                let player = colour(0)?;
+                let state = self.state().clone();
+                if state.is_over() != MoveResult::Good {
+                    return Err("game is over".to_string());
+                }
+                if state.current_player() != player {
+                    return Err(format!("it is {}'s turn", colour_name(state.current_player())));
+                }
+
+                let start = Instant::now();
+                let action = match self.budget(&state) {
+                    Some(budget) => Action::Place(self.ai.choose_timed(&state, player, budget)),
+                    None => self.ai.act(&state, player),
+                };
+                if let Some(left) = self.time_left[index(player)] {
+                    self.time_left[index(player)] = Some(left - ::std::cmp::min(left, start.elapsed()));
+                }
+
+                let pos = match action {
+                    Action::Place(pos) => pos,
+                    Action::Swap => state.swap_available().ok_or("swap is not allowed".to_string())?,
+                    Action::Resign => {
+                        self.history.push(state.with_action(Action::Resign));
+                        return Ok("resign".to_string());
+                    }
+                    // Draw offers have no place in the protocol, so an ai
+                    // that wants a draw has to play on.
+                    Action::OfferDraw | Action::AcceptDraw => self.ai.choose(&state, player),
+                };
+                self.play(player, pos)?;
+                Ok(state.move_name(pos))
+            }
+            "undo" => {
+                if self.history.len() < 2 {
+                    return Err("cannot undo".to_string());
+                }
+                self.history.pop();
+                Ok(String::new())
+            }
+            "showboard" => {
+                let state = self.state();
+                Ok(format!("{}\n{}", state.to_notation(), render_ascii(state, true).trim_end()))
+            }
+            "time_settings" => {
+                let settings = TimeSettings {
+                    main: seconds(arg(0)?)?,
+                    byo_yomi: seconds(arg(1)?)?,
+                    stones: arg(2)?.parse().map_err(|_| "bad stones".to_string())?,
+                };
+                let unlimited = settings.main == Duration::from_millis(0) &&
+                                (settings.byo_yomi == Duration::from_millis(0) || settings.stones == 0);
+                self.time = if unlimited { None } else { Some(settings) };
+                self.time_left = [Some(settings.main), Some(settings.main)];
+                Ok(String::new())
+            }
+            "time_left" => {
+                let player = colour(0)?;
+                self.time_left[index(player)] = Some(seconds(arg(1)?)?);
+                Ok(String::new())
+            }
+            "final_score" => Ok(match self.state().is_over() {
+                MoveResult::End(Player::Red) => "R+",
+                MoveResult::End(Player::Green) => "G+",
+                MoveResult::Resigned(Player::Green) => "R+Resign",
+                MoveResult::Resigned(Player::Red) => "G+Resign",
+                MoveResult::Tie | MoveResult::Draw => "0",
+                _ => "?",
+            }.to_string()),
+            _ => Err("unknown command".to_string()),
+        }
+    }
+
+    fn play(&mut self, player: Player, pos: HexPosition) -> Result<(), String> {
+        let mut next = self.state().clone();
+        if next.is_over() != MoveResult::Good {
+            return Err("game is over".to_string());
+        }
+        if next.current_player() != player {
+            return Err(format!("it is {}'s turn", colour_name(next.current_player())));
+        }
+        match next.make_move(&pos) {
+            MoveResult::Bad => Err("illegal move".to_string()),
+            _ => {
+                self.history.push(next);
+                Ok(())
+            }
+        }
+    }
+
+    // Spreads the remaining main time over our expected remaining moves.
+    fn budget(&self, state: &GameState) -> Option<Duration> {
+        let settings = match self.time {
+            Some(settings) => settings,
+            None => return None,
+        };
+        let left = self.time_left[index(state.current_player())].unwrap_or(settings.main);
+        let open = state.map().grid().iter().filter(|pos| !state.map().contains(pos)).count() as u32;
+        let mut budget = left / ::std::cmp::max((open + 1) / 2, 1);
+        if settings.stones > 0 {
+            budget = budget + settings.byo_yomi / settings.stones;
+        }
+        Some(budget)
+    }
+}
+
+/// The other end of the protocol: an engine running as a child process.
+pub struct RemoteEngine {
+    pub command: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+#[derive(Debug)]
+pub enum RemoteError {
+    Timeout,
+    Failed(String),
+    Disconnected,
+}
+
+impl RemoteEngine {
+    /// Starts `command`, split on whitespace into a program and arguments.
+    pub fn spawn(command: &str) -> io::Result<RemoteEngine> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or(io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+        let mut child = Command::new(program)
+            .args(&words.collect::<Vec<_>>())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (s, r) = channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                match line {
+                    Ok(line) => if s.send(line).is_err() {
+                        return;
+                    },
+                    Err(_) => return,
+                }
+            }
+        });
+
+        Ok(RemoteEngine {
+            command: command.to_string(),
+            child: child,
+            stdin: stdin,
+            lines: r,
+        })
+    }
+
+    /// Sends one command and waits for its response, giving up after
+    /// `timeout` if one is given.
+    pub fn send(&mut self, command: &str, timeout: Option<Duration>) -> Result<String, RemoteError> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| RemoteError::Disconnected)?;
+
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut response: Option<(bool, Vec<String>)> = None;
+        loop {
+            let line = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    let wait = if deadline > now { deadline - now } else { Duration::from_millis(0) };
+                    match self.lines.recv_timeout(wait) {
+                        Ok(line) => line,
+                        Err(RecvTimeoutError::Timeout) => return Err(RemoteError::Timeout),
+                        Err(RecvTimeoutError::Disconnected) => return Err(RemoteError::Disconnected),
+                    }
+                }
+                None => self.lines.recv().map_err(|_| RemoteError::Disconnected)?,
+            };
+
+            let line = line.trim_end().to_string();
+            response = match response {
+                None if line.starts_with('=') || line.starts_with('?') => {
+                    let text = line[1 ..].trim_start_matches(|c: char| c.is_ascii_digit()).trim().to_string();
+                    Some((line.starts_with('='), vec![text]))
+                }
+                None => None,
+                Some((ok, lines)) => {
+                    if line.is_empty() {
+                        let text = lines.join("\n");
+                        return if ok { Ok(text) } else { Err(RemoteError::Failed(text)) };
+                    }
+                    let mut lines = lines;
+                    lines.push(line);
+                    Some((ok, lines))
+                }
+            };
+        }
+    }
+}
+
+impl Drop for RemoteEngine {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "quit");
+        let _ = self.stdin.flush();
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
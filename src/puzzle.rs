@@ -0,0 +1,177 @@
+//! Puzzles: positions with exactly one right move.
+//!
+//! There are two kinds.  In a "win in N" puzzle the side to move can force
+//! a win with their Nth move, and only one first move does it.  In an
+//! "avoid the trap" puzzle every move that doesn't lose on the spot hands
+//! the opponent a forced win, except one.
+//!
+//! Puzzles are written one a line as the position, the kind and the
+//! solution, separated by `|`:
+//!
+//! ```text
+//! 5/6/1rgg3/2grr3/3gg2g1/1r6/rr5/r5/5 g 4 d5 | win 2 | e2
+//! rr3/6/7/g7/1g7/8/1gr4/6/5 r 4 d1 | trap | f3
+//! ```
+
+use super::*;
+use solver::{forced_win, winning_moves};
+use std::fmt;
+
+/// How far ahead the opponent's wins are looked for when deciding whether
+/// a move falls into a trap: their move, ours and theirs again.
+pub const TRAP_PLIES: usize = 3;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PuzzleKind {
+    /// A forced win on the side to move's Nth move.
+    WinIn(usize),
+    AvoidTrap,
+}
+
+#[derive(Clone)]
+pub struct Puzzle {
+    pub state: GameState,
+    pub kind: PuzzleKind,
+    pub solution: HexPosition,
+}
+
+fn plies(moves: usize) -> usize {
+    2 * moves - 1
+}
+
+// Whether playing `mv` loses: on the spot, or by letting the opponent force
+// a win within `TRAP_PLIES`.
+fn poisoned(state: &GameState, mv: &HexPosition) -> bool {
+    let next = state.with_move(mv);
+    match next.is_over() {
+        MoveResult::End(p) => p != state.current_player(),
+        MoveResult::Good => forced_win(&next, TRAP_PLIES),
+        _ => false,
+    }
+}
+
+// A move that doesn't lose on the spot by making three in a row.
+fn natural(state: &GameState, mv: &HexPosition) -> bool {
+    match state.with_move(mv).is_over() {
+        MoveResult::End(p) => p == state.current_player(),
+        _ => true,
+    }
+}
+
+impl Puzzle {
+    /// The unique forced win in `state` needing the fewest moves, up to
+    /// `max_moves`.
+    pub fn win_in(state: &GameState, max_moves: usize) -> Option<Puzzle> {
+        for moves in 1 .. max_moves + 1 {
+            let wins = winning_moves(state, plies(moves), false);
+            match wins.len() {
+                0 => continue,
+                1 => return Some(Puzzle {
+                    state: state.clone(),
+                    kind: PuzzleKind::WinIn(moves),
+                    solution: wins[0],
+                }),
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// The one safe move in `state`, if there are at least `min_natural`
+    /// natural looking moves and all of the others are traps.  Positions
+    /// with a forced win don't count.
+    pub fn avoid_trap(state: &GameState, min_natural: usize) -> Option<Puzzle> {
+        if state.is_over() != MoveResult::Good || forced_win(state, TRAP_PLIES) {
+            return None;
+        }
+        let natural: Vec<_> = state.legal_moves().into_iter().filter(|mv| natural(state, mv)).collect();
+        if natural.len() < min_natural {
+            return None;
+        }
+        let mut safe = natural.into_iter().filter(|mv| !poisoned(state, mv));
+        match (safe.next(), safe.next()) {
+            (Some(mv), None) => Some(Puzzle {
+                state: state.clone(),
+                kind: PuzzleKind::AvoidTrap,
+                solution: mv,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The natural looking moves that lose, for trap puzzles.
+    pub fn traps(&self) -> Vec<HexPosition> {
+        self.state.legal_moves().into_iter()
+            .filter(|mv| natural(&self.state, mv) && poisoned(&self.state, mv))
+            .collect()
+    }
+
+    /// Checks the puzzle from scratch: that the solution works and that no
+    /// other move does.
+    pub fn verify(&self) -> Result<(), String> {
+        if !self.state.is_legal(&self.solution) {
+            return Err(format!("{} is not a legal move", self.state.move_name(self.solution)));
+        }
+        let found = match self.kind {
+            PuzzleKind::WinIn(moves) => {
+                if moves > 1 && forced_win(&self.state, plies(moves - 1)) {
+                    return Err(format!("there is a win in fewer than {} moves", moves));
+                }
+                winning_moves(&self.state, plies(moves), false)
+            }
+            PuzzleKind::AvoidTrap => {
+                if forced_win(&self.state, TRAP_PLIES) {
+                    return Err("there is a forced win".to_string());
+                }
+                self.state.legal_moves().into_iter()
+                    .filter(|mv| natural(&self.state, mv) && !poisoned(&self.state, mv))
+                    .collect()
+            }
+        };
+        match found.len() {
+            1 if found[0] == self.solution => Ok(()),
+            1 => Err(format!("the solution is {}", self.state.move_name(found[0]))),
+            0 => Err("there is no solution".to_string()),
+            n => Err(format!("there are {} solutions", n)),
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Puzzle, String> {
+        let fields: Vec<_> = line.split('|').map(str::trim).collect();
+        if fields.len() != 3 {
+            return Err(format!("expected POSITION | KIND | MOVE: {}", line));
+        }
+        let state = GameState::from_notation(fields[0]).map_err(|e| e.to_string())?;
+        let kind = match fields[1] {
+            "trap" => PuzzleKind::AvoidTrap,
+            kind if kind.starts_with("win ") => {
+                match kind[4 ..].trim().parse() {
+                    Ok(n) if n > 0 => PuzzleKind::WinIn(n),
+                    _ => return Err(format!("bad puzzle kind: {}", kind)),
+                }
+            }
+            kind => return Err(format!("bad puzzle kind: {}", kind)),
+        };
+        let solution = state.parse_move(fields[2]).map_err(|e| e.to_string())?;
+        Ok(Puzzle {
+            state: state,
+            kind: kind,
+            solution: solution,
+        })
+    }
+}
+
+impl fmt::Display for PuzzleKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PuzzleKind::WinIn(n) => write!(f, "win {}", n),
+            PuzzleKind::AvoidTrap => write!(f, "trap"),
+        }
+    }
+}
+
+impl fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} | {} | {}", self.state.to_notation(), self.kind, self.state.move_name(self.solution))
+    }
+}
@@ -0,0 +1,172 @@
+extern crate hexgame;
+extern crate pbr;
+
+use hexgame::{Config, GameState, MoveResult};
+use hexgame::ai::from_spec;
+use hexgame::puzzle::{Puzzle, PuzzleKind};
+use hexgame::record::GameRecord;
+use hexgame::solver::Keys;
+use hexgame::suite::SuiteEntry;
+use hexgame::training;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+const USAGE: &'static str = "\
+usage: puzzles generate [--games N] [--ai SPEC] [--random-moves K] [--seed N]
+                        [--max-moves M] [--min-natural C]
+                        [--records RECORD_FILE]... OUTPUT
+       puzzles verify FILE
+       puzzles epd FILE
+
+generate looks through N games (default 50) of the ai against itself,
+default ranker:2, each opening with K random moves (default 4), and through
+the records given.  Every position with a unique forced win in at most M
+moves (default 2) becomes a `win` puzzle; failing that, one where at least
+C moves (default 3) don't lose on the spot but all except one walk into a
+forced win for the opponent becomes a `trap` puzzle.  Positions that are
+the same up to symmetry are only written once.
+
+verify checks every puzzle in FILE again, as described in
+`hexgame::puzzle`, and lists the ones that fail.
+
+epd prints the puzzles in FILE as a test suite for the tactics binary, as
+described in `hexgame::suite`, numbering them within each kind.";
+
+fn parse<T: ::std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    value.and_then(|v| v.parse().ok()).ok_or(format!("bad value for {}", flag))
+}
+
+fn generate(args: &[String]) -> Result<(), String> {
+    let mut games = 50;
+    let mut spec = "ranker:2".to_string();
+    let mut random_moves = 4;
+    let mut seed = 1;
+    let mut max_moves = 2;
+    let mut min_natural = 3;
+    let mut records = vec![];
+    let mut output = None;
+
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--games" => games = parse(&arg, args.next())?,
+            "--ai" => spec = args.next().ok_or("missing ai".to_string())?,
+            "--random-moves" => random_moves = parse(&arg, args.next())?,
+            "--seed" => seed = parse(&arg, args.next())?,
+            "--max-moves" => max_moves = parse(&arg, args.next())?,
+            "--min-natural" => min_natural = parse(&arg, args.next())?,
+            "--records" => records.push(args.next().ok_or("missing record file".to_string())?),
+            _ if output.is_none() => output = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    let output = output.ok_or("missing OUTPUT".to_string())?;
+    let config = Config::standard();
+
+    let mut positions: Vec<GameState> = vec![];
+    for path in &records {
+        let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+        for record in GameRecord::read(BufReader::new(file)) {
+            let record = record.map_err(|e| format!("{}: {}", path, e))?;
+            if record.config.radius == config.radius {
+                positions.extend(record.states());
+            }
+        }
+    }
+    let mut red = from_spec(&spec)?;
+    let mut green = from_spec(&spec)?;
+    let mut rng = training::seeded_rng(seed);
+    for _ in 0 .. games {
+        positions.extend(training::self_play(&mut *red, &mut *green, config, random_moves, &mut rng).states());
+    }
+
+    let keys = Keys::new(config.radius)?;
+    let mut seen = HashSet::new();
+    let mut file = File::create(&output).map_err(|e| format!("couldn't create {}: {}", output, e))?;
+    let (mut wins, mut traps) = (0, 0);
+    let mut pbr = pbr::ProgressBar::new(positions.len() as u64);
+
+    for state in &positions {
+        pbr.inc();
+        if state.is_over() != MoveResult::Good || !seen.insert(keys.key(state)) {
+            continue;
+        }
+        let puzzle = match Puzzle::win_in(state, max_moves) {
+            Some(puzzle) => puzzle,
+            None => match Puzzle::avoid_trap(state, min_natural) {
+                Some(puzzle) => puzzle,
+                None => continue,
+            },
+        };
+        match puzzle.kind {
+            PuzzleKind::WinIn(_) => wins += 1,
+            PuzzleKind::AvoidTrap => traps += 1,
+        }
+        writeln!(file, "{}", puzzle).map_err(|e| format!("couldn't write {}: {}", output, e))?;
+    }
+    println!("\n{} win puzzles and {} trap puzzles from {} positions", wins, traps, seen.len());
+    Ok(())
+}
+
+fn verify(args: &[String]) -> Result<(), String> {
+    let path = args.get(0).ok_or("expected FILE".to_string())?;
+    let (mut good, mut bad) = (0, 0);
+    for (line, puzzle) in read_puzzles(path)? {
+        match puzzle.and_then(|p| p.verify()) {
+            Ok(()) => good += 1,
+            Err(e) => {
+                println!("{}:{}: {}", path, line, e);
+                bad += 1;
+            }
+        }
+    }
+    println!("{} good, {} bad", good, bad);
+    if bad > 0 {
+        return Err(format!("{} puzzles failed", bad));
+    }
+    Ok(())
+}
+
+fn read_puzzles(path: &str) -> Result<Vec<(usize, Result<Puzzle, String>)>, String> {
+    let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+    let mut puzzles = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("couldn't read {}: {}", path, e))?;
+        if !line.trim().is_empty() && !line.starts_with('#') {
+            puzzles.push((i + 1, Puzzle::parse(&line)));
+        }
+    }
+    Ok(puzzles)
+}
+
+fn epd(args: &[String]) -> Result<(), String> {
+    let path = args.get(0).ok_or("expected FILE".to_string())?;
+    let mut counts = HashMap::new();
+    for (line, puzzle) in read_puzzles(path)? {
+        let puzzle = puzzle.map_err(|e| format!("{}:{}: {}", path, line, e))?;
+        let group = match puzzle.kind {
+            PuzzleKind::WinIn(n) => format!("win{}", n),
+            PuzzleKind::AvoidTrap => "trap".to_string(),
+        };
+        let count = counts.entry(group.clone()).or_insert(0);
+        *count += 1;
+        println!("{}", SuiteEntry::from_puzzle(&puzzle, &format!("{}-{:03}", group, count)));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<_> = ::std::env::args().skip(1).collect();
+    let result = match args.get(0).map(|s| &s[..]) {
+        Some("generate") => generate(&args[1 ..]),
+        Some("verify") => verify(&args[1 ..]),
+        Some("epd") => epd(&args[1 ..]),
+        _ => Err("expected generate, verify or epd".to_string()),
+    };
+    if let Err(e) = result {
+        println!("{}\n{}", e, USAGE);
+        ::std::process::exit(1);
+    }
+}
@@ -0,0 +1,315 @@
+//! Game records in a PGN-like text format.
+//!
+//! A record is a block of `[Tag "value"]` lines followed by one move per
+//! line, in the cell notation from `notation`, and terminated by a blank
+//! line:
+//!
+//! ```text
+//! [Red "RankerAi { recursion_limit: 4, .. }"]
+//! [Green "human"]
+//! [Radius "4"]
+//! [Timestamp "1476825600"]
+//! [Result "Red"]
+//!
+//! e5 {12ms}
+//! d6 {40ms}
+//! ```
+//!
+//! The radius runs from 1 to `MAX_RADIUS`.  Games played with the swap
+//! rule have a `[Swap "yes"]` tag, and the swap itself is written as the
+//! move `swap`.
+//!
+//! The result is `Red`, `Green` or `Tie`, `RedFlagged` or `GreenFlagged`
+//! when that player ran out of time, `RedResigned` or `GreenResigned`,
+//! `Draw` by agreement, or `*` for an unfinished game.
+//!
+//! Any lines outside of a record are ignored by the reader, so records can be
+//! interleaved with other program output.
+
+use super::*;
+use notation::Cell;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    pub red: String,
+    pub green: String,
+    pub config: Config,
+    /// Seconds since the unix epoch at which the game started.
+    pub timestamp: u64,
+    pub moves: Vec<RecordedMove>,
+    pub result: Option<MoveResult>,
+}
+
+#[derive(Copy, Clone)]
+pub struct RecordedMove {
+    pub position: HexPosition,
+    pub time: Duration,
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    Io(io::Error),
+    BadTag(String),
+    BadMove(String),
+}
+
+impl GameRecord {
+    pub fn new<A: Into<String>, B: Into<String>>(red: A, green: B, config: Config) -> GameRecord {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        GameRecord {
+            red: red.into(),
+            green: green.into(),
+            config: config,
+            timestamp: timestamp,
+            moves: vec![],
+            result: None,
+        }
+    }
+
+    pub fn push(&mut self, position: HexPosition, time: Duration) {
+        self.moves.push(RecordedMove {
+            position: position,
+            time: time,
+        });
+    }
+
+    /// Every position of the game, starting with the empty board.
+    pub fn states(&self) -> Vec<GameState> {
+        let mut state = GameState::with_config(self.config);
+        let mut out = vec![state.clone()];
+        for mv in &self.moves {
+            state.make_move(&mv.position);
+            out.push(state.clone());
+        }
+        out
+    }
+
+    pub fn final_state(&self) -> GameState {
+        self.states().pop().unwrap()
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Records<R> {
+        Records {
+            lines: reader.lines(),
+        }
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_tag(f, "Red", &self.red)?;
+        write_tag(f, "Green", &self.green)?;
+        write_tag(f, "Radius", &self.config.radius.to_string())?;
+        if self.config.swap {
+            write_tag(f, "Swap", "yes")?;
+        }
+        write_tag(f, "Timestamp", &self.timestamp.to_string())?;
+        write_tag(f, "Result", result_to_str(self.result))?;
+        writeln!(f, "")?;
+        for (i, mv) in self.moves.iter().enumerate() {
+            let ms = mv.time.as_secs() * 1000 + mv.time.subsec_nanos() as u64 / 1_000_000;
+            if self.config.swap && i == 1 && mv.position == self.moves[0].position {
+                writeln!(f, "swap {{{}ms}}", ms)?;
+            } else {
+                writeln!(f, "{} {{{}ms}}", Cell::new(mv.position, self.config), ms)?;
+            }
+        }
+        writeln!(f, "")
+    }
+}
+
+fn write_tag(f: &mut fmt::Formatter, name: &str, value: &str) -> fmt::Result {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    writeln!(f, "[{} \"{}\"]", name, value)
+}
+
+fn parse_tag(line: &str) -> Option<(&str, String)> {
+    if !line.starts_with('[') || !line.ends_with(']') {
+        return None;
+    }
+    let inner = &line[1 .. line.len() - 1];
+    let space = match inner.find(' ') {
+        Some(i) => i,
+        None => return None,
+    };
+    let (name, value) = (&inner[.. space], inner[space ..].trim());
+    if !value.starts_with('"') || !value.ends_with('"') || value.len() < 2 {
+        return None;
+    }
+
+    let mut out = String::new();
+    let mut escaped = false;
+    for c in value[1 .. value.len() - 1].chars() {
+        match (escaped, c) {
+            (false, '\\') => escaped = true,
+            (_, c) => {
+                out.push(c);
+                escaped = false;
+            }
+        }
+    }
+    Some((name, out))
+}
+
+// `first` is the first move of the game, which is what a swap takes over.
+fn parse_move(line: &str, config: Config, first: Option<HexPosition>) -> Option<RecordedMove> {
+    let (pos, time) = match line.find('{') {
+        Some(i) => (line[.. i].trim(), Some(line[i ..].trim())),
+        None => (line.trim(), None),
+    };
+
+    let position = match (pos, first) {
+        ("swap", Some(first)) if config.swap => first,
+        _ => match Cell::parse(pos, config) {
+            Ok(cell) => cell.position,
+            Err(_) => return None,
+        },
+    };
+
+    let time = match time {
+        Some(time) => {
+            if !time.starts_with('{') || !time.ends_with("ms}") {
+                return None;
+            }
+            match time[1 .. time.len() - 3].trim().parse() {
+                Ok(ms) => Duration::from_millis(ms),
+                Err(_) => return None,
+            }
+        }
+        None => Duration::from_millis(0),
+    };
+
+    Some(RecordedMove {
+        position: position,
+        time: time,
+    })
+}
+
+pub fn result_to_str(result: Option<MoveResult>) -> &'static str {
+    match result {
+        Some(MoveResult::End(Player::Red)) => "Red",
+        Some(MoveResult::End(Player::Green)) => "Green",
+        Some(MoveResult::Tie) => "Tie",
+        Some(MoveResult::Flagged(Player::Red)) => "RedFlagged",
+        Some(MoveResult::Flagged(Player::Green)) => "GreenFlagged",
+        Some(MoveResult::Resigned(Player::Red)) => "RedResigned",
+        Some(MoveResult::Resigned(Player::Green)) => "GreenResigned",
+        Some(MoveResult::Draw) => "Draw",
+        _ => "*",
+    }
+}
+
+pub fn result_from_str(s: &str) -> Option<Option<MoveResult>> {
+    match s {
+        "Red" => Some(Some(MoveResult::End(Player::Red))),
+        "Green" => Some(Some(MoveResult::End(Player::Green))),
+        "Tie" => Some(Some(MoveResult::Tie)),
+        "RedFlagged" => Some(Some(MoveResult::Flagged(Player::Red))),
+        "GreenFlagged" => Some(Some(MoveResult::Flagged(Player::Green))),
+        "RedResigned" => Some(Some(MoveResult::Resigned(Player::Red))),
+        "GreenResigned" => Some(Some(MoveResult::Resigned(Player::Green))),
+        "Draw" => Some(Some(MoveResult::Draw)),
+        "*" => Some(None),
+        _ => None,
+    }
+}
+
+/// Iterator over the records in a stream, created by `GameRecord::read`.
+pub struct Records<R> {
+    lines: io::Lines<R>,
+}
+
+impl <R: BufRead> Iterator for Records<R> {
+    type Item = Result<GameRecord, ParseError>;
+
+    fn next(&mut self) -> Option<Result<GameRecord, ParseError>> {
+        let mut record: Option<GameRecord> = None;
+        let mut in_moves = false;
+
+        while let Some(line) = self.lines.next() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(ParseError::Io(e))),
+            };
+            // Progress bars and the like may share the stream; only look at
+            // what comes after the last carriage return.
+            let line = line.rsplit('\r').next().unwrap_or("").trim();
+
+            if record.is_none() {
+                if parse_tag(line).is_none() {
+                    continue;
+                }
+                record = Some(GameRecord {
+                    red: String::new(),
+                    green: String::new(),
+                    config: Config::standard(),
+                    timestamp: 0,
+                    moves: vec![],
+                    result: None,
+                });
+            }
+
+            let rec = record.as_mut().unwrap();
+            if line.is_empty() {
+                if in_moves {
+                    break;
+                }
+                in_moves = true;
+                continue;
+            }
+
+            if !in_moves {
+                let (name, value) = match parse_tag(line) {
+                    Some(tag) => tag,
+                    None => return Some(Err(ParseError::BadTag(line.to_string()))),
+                };
+                let ok = match name {
+                    "Red" => { rec.red = value; true }
+                    "Green" => { rec.green = value; true }
+                    "Radius" => match value.parse() {
+                        Ok(r) if r >= 1 && r <= MAX_RADIUS => { rec.config.radius = r; true }
+                        _ => false,
+                    },
+                    "Swap" => { rec.config.swap = value == "yes"; true }
+                    "Timestamp" => value.parse().map(|t| rec.timestamp = t).is_ok(),
+                    "Result" => result_from_str(&value).map(|r| rec.result = r).is_some(),
+                    _ => true,
+                };
+                if !ok {
+                    return Some(Err(ParseError::BadTag(line.to_string())));
+                }
+            } else {
+                let first = if rec.moves.len() == 1 { Some(rec.moves[0].position) } else { None };
+                match parse_move(line, rec.config, first) {
+                    Some(mv) => rec.moves.push(mv),
+                    None => return Some(Err(ParseError::BadMove(line.to_string()))),
+                }
+            }
+        }
+
+        record.map(Ok)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Io(ref e) => write!(f, "io error: {}", e),
+            ParseError::BadTag(ref line) => write!(f, "bad tag: {}", line),
+            ParseError::BadMove(ref line) => write!(f, "bad move: {}", line),
+        }
+    }
+}
+
+impl fmt::Debug for RecordedMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (q, r) = self.position.as_axial();
+        write!(f, "RecordedMove({},{}, {:?})", q, r, self.time)
+    }
+}
@@ -0,0 +1,397 @@
+extern crate hexgame;
+
+use hexgame::{GameState, Player, MoveResult};
+use hexgame::net::{ClientMessage, ServerMessage, DEFAULT_ADDRESS, single_word};
+use hexgame::record::GameRecord;
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &'static str = "\
+usage: server [ADDRESS]
+
+Hosts games between clients speaking the protocol in `hexgame::net`, such
+as `play --connect`.  Players are paired up in the order they join.
+Games can also be hosted by a single client playing both sides, as
+`survival --broadcast` does, and any game can be watched by spectators
+such as `vis --spectate`.  Finished games are printed to stdout as game
+records.  ADDRESS defaults to 127.0.0.1:7700.";
+
+// A client that stops reading is dropped once a write to it times out,
+// rather than stalling everyone.
+const WRITE_TIMEOUT_MS: u64 = 2000;
+
+enum Event {
+    Connected(usize, TcpStream),
+    Line(usize, String),
+    Disconnected(usize),
+}
+
+struct Client {
+    writer: TcpStream,
+    seat: Option<usize>,
+    watching: Option<usize>,
+    following: bool,
+}
+
+struct Game {
+    state: GameState,
+    players: [Option<usize>; 2],
+    spectators: Vec<usize>,
+    record: GameRecord,
+    last_move: Instant,
+    over: bool,
+}
+
+impl Game {
+    fn started(&self) -> bool {
+        self.players[1].is_some()
+    }
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::Red => 0,
+        Player::Green => 1,
+    }
+}
+
+fn handle_connection(id: usize, stream: TcpStream, events: Sender<Event>) {
+    let reader = match stream.try_clone() {
+        Ok(reader) => BufReader::new(reader),
+        Err(_) => return,
+    };
+    let _ = stream.set_write_timeout(Some(Duration::from_millis(WRITE_TIMEOUT_MS)));
+    if events.send(Event::Connected(id, stream)).is_err() {
+        return;
+    }
+    for line in reader.lines() {
+        match line {
+            Ok(line) => if events.send(Event::Line(id, line)).is_err() {
+                return;
+            },
+            Err(_) => break,
+        }
+    }
+    let _ = events.send(Event::Disconnected(id));
+}
+
+struct Server {
+    clients: HashMap<usize, Client>,
+    games: Vec<Game>,
+    // Clients dropped while handling an event, with their seats, to be
+    // taken out of their games once it's done.
+    dropped: Vec<(usize, Option<usize>)>,
+}
+
+impl Server {
+    fn in_game(&self, client: usize) -> bool {
+        match self.clients.get(&client).and_then(|c| c.seat) {
+            Some(game) => !self.games[game].over,
+            None => false,
+        }
+    }
+
+    fn send(&mut self, client: usize, message: &ServerMessage) {
+        let failed = match self.clients.get_mut(&client) {
+            Some(c) => writeln!(c.writer, "{}", message).is_err(),
+            None => false,
+        };
+        if failed {
+            self.drop_client(client);
+        }
+    }
+
+    // Forgets a client that has disconnected or can't be written to.
+    fn drop_client(&mut self, client: usize) {
+        if let Some(c) = self.clients.remove(&client) {
+            let _ = c.writer.shutdown(Shutdown::Both);
+            for game in &mut self.games {
+                game.spectators.retain(|&s| s != client);
+            }
+            self.dropped.push((client, c.seat));
+        }
+    }
+
+    // Sends to both players, once each if they're the same client, and to
+    // the spectators.
+    fn broadcast(&mut self, game: usize, message: &ServerMessage) {
+        let mut audience = self.games[game].spectators.clone();
+        for player in self.games[game].players.iter().filter_map(|&p| p) {
+            if !audience.contains(&player) {
+                audience.push(player);
+            }
+        }
+        for client in audience {
+            self.send(client, message);
+        }
+    }
+
+    fn finish(&mut self, game: usize, result: Option<MoveResult>, reason: &str) {
+        self.games[game].over = true;
+        self.games[game].record.result = result;
+        self.broadcast(game, &ServerMessage::Over(result, reason.to_string()));
+        if result.is_some() {
+            println!("{}", self.games[game].record);
+        }
+    }
+
+    fn create(&mut self, red: usize, red_name: String) -> usize {
+        let state = GameState::new();
+        self.games.push(Game {
+            record: GameRecord::new(red_name, String::new(), state.config()),
+            state: state,
+            players: [Some(red), None],
+            spectators: vec![],
+            last_move: Instant::now(),
+            over: false,
+        });
+        self.games.len() - 1
+    }
+
+    fn start(&mut self, game: usize, green: usize, green_name: String) {
+        let position = {
+            let g = &mut self.games[game];
+            g.players[1] = Some(green);
+            g.record.green = green_name;
+            g.last_move = Instant::now();
+            g.state.to_notation()
+        };
+        let players = self.games[game].players;
+        self.send(players[0].unwrap(), &ServerMessage::Position(position.clone()));
+        if players[1] != players[0] {
+            self.send(green, &ServerMessage::Position(position));
+        }
+
+        let mut spectators = self.games[game].spectators.clone();
+        spectators.extend(self.clients.iter().filter(|&(_, c)| c.following).map(|(&id, _)| id));
+        for spectator in spectators {
+            self.watch(spectator, game);
+        }
+    }
+
+    fn join(&mut self, client: usize, name: String) {
+        let open = self.games.iter().position(|g| !g.over && !g.started());
+        let (game, player) = match open {
+            Some(game) => (game, Player::Green),
+            None => (self.create(client, name.clone()), Player::Red),
+        };
+        self.clients.get_mut(&client).unwrap().seat = Some(game);
+        self.send(client, &ServerMessage::Welcome(game, player));
+        if player == Player::Green {
+            self.start(game, client, name);
+        }
+    }
+
+    fn host(&mut self, client: usize, red: String, green: String) {
+        let game = self.create(client, red);
+        self.clients.get_mut(&client).unwrap().seat = Some(game);
+        self.send(client, &ServerMessage::Welcome(game, Player::Red));
+        self.start(game, client, green);
+    }
+
+    // Moves the client to spectating `game`, and replays the game so far.
+    fn watch(&mut self, client: usize, game: usize) {
+        // A follower may have been dropped while the game was starting.
+        if !self.clients.contains_key(&client) {
+            return;
+        }
+        if let Some(old) = self.clients[&client].watching {
+            self.games[old].spectators.retain(|&c| c != client);
+        }
+        self.clients.get_mut(&client).unwrap().watching = Some(game);
+        self.games[game].spectators.push(client);
+
+        if !self.games[game].started() {
+            return;
+        }
+        let mut messages = {
+            let record = &self.games[game].record;
+            let states = record.states();
+            let mut messages = vec![
+                ServerMessage::Watching(game, record.red.clone(), record.green.clone()),
+                ServerMessage::Position(states[0].to_notation()),
+            ];
+            for (state, mv) in states.iter().zip(&record.moves) {
+                messages.push(ServerMessage::Moved(state.current_player(), state.move_name(mv.position)));
+            }
+            messages
+        };
+        if self.games[game].over {
+            messages.push(ServerMessage::Over(self.games[game].record.result, "finished".to_string()));
+        }
+        for message in messages {
+            self.send(client, &message);
+        }
+    }
+
+    fn spectate(&mut self, client: usize, game: Option<usize>) -> Result<(), String> {
+        match game {
+            Some(game) if game < self.games.len() => {
+                self.clients.get_mut(&client).unwrap().following = false;
+                self.watch(client, game);
+                Ok(())
+            }
+            Some(game) => Err(format!("no game {}", game)),
+            None => {
+                self.clients.get_mut(&client).unwrap().following = true;
+                match self.games.iter().rposition(|g| g.started() && !g.over) {
+                    Some(game) => self.watch(client, game),
+                    None => {
+                        if let Some(old) = self.clients[&client].watching {
+                            self.games[old].spectators.retain(|&c| c != client);
+                        }
+                        self.clients.get_mut(&client).unwrap().watching = None;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn play(&mut self, client: usize, cell: &str) -> Result<(), String> {
+        let game = match self.clients[&client].seat {
+            Some(game) => game,
+            None => return Err("not in a game".to_string()),
+        };
+        let (player, name, result) = {
+            let g = &mut self.games[game];
+            if g.over || !g.started() {
+                return Err("game is not in progress".to_string());
+            }
+            let player = g.state.current_player();
+            if g.players[index(player)] != Some(client) {
+                return Err("not your turn".to_string());
+            }
+            let pos = g.state.parse_move(cell).map_err(|e| e.to_string())?;
+            let name = g.state.move_name(pos);
+            let result = g.state.make_move(&pos);
+            if result == MoveResult::Bad {
+                return Err(format!("illegal move {}", cell));
+            }
+            g.record.push(pos, g.last_move.elapsed());
+            g.last_move = Instant::now();
+            (player, name, result)
+        };
+
+        self.broadcast(game, &ServerMessage::Moved(player, name));
+        if result != MoveResult::Good {
+            self.finish(game, Some(result), "rules");
+        }
+        Ok(())
+    }
+
+    // In a hosted game it's the side to move that resigns.
+    fn resign(&mut self, client: usize) -> Result<(), String> {
+        let game = match self.clients[&client].seat {
+            Some(game) if !self.games[game].over && self.games[game].started() => game,
+            _ => return Err("game is not in progress".to_string()),
+        };
+        let players = self.games[game].players;
+        let player = if players[0] == players[1] {
+            self.games[game].state.current_player()
+        } else if players[0] == Some(client) {
+            Player::Red
+        } else {
+            Player::Green
+        };
+        self.finish(game, Some(MoveResult::Resigned(player)), "resigned");
+        Ok(())
+    }
+
+    // A player who disconnects loses a game in progress; a game still
+    // waiting for an opponent, or one hosted by a single client, is simply
+    // abandoned.
+    fn leave(&mut self, client: usize, seat: Option<usize>, reason: &str) {
+        let game = match seat {
+            Some(game) => game,
+            None => return,
+        };
+        if self.games[game].over {
+            return;
+        }
+        let players = self.games[game].players;
+        if players[0] == players[1] || !self.games[game].started() {
+            self.finish(game, None, "abandoned");
+            return;
+        }
+        let player = if players[0] == Some(client) { Player::Red } else { Player::Green };
+        self.finish(game, Some(MoveResult::End(player.inverse())), reason);
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Connected(id, writer) => {
+                self.clients.insert(id, Client {
+                    writer: writer,
+                    seat: None,
+                    watching: None,
+                    following: false,
+                });
+            }
+            Event::Line(id, _) if !self.clients.contains_key(&id) => {}
+            Event::Line(id, line) => {
+                let reply = match ClientMessage::parse(&line) {
+                    Some(ClientMessage::Play(_)) |
+                    Some(ClientMessage::Host(..)) if self.in_game(id) => {
+                        Err("already in a game".to_string())
+                    }
+                    Some(ClientMessage::Play(name)) => {
+                        self.join(id, single_word(&name));
+                        Ok(())
+                    }
+                    Some(ClientMessage::Host(red, green)) => {
+                        self.host(id, red, green);
+                        Ok(())
+                    }
+                    Some(ClientMessage::Watch(game)) => self.spectate(id, game),
+                    Some(ClientMessage::Move(cell)) => self.play(id, &cell),
+                    Some(ClientMessage::Resign) => self.resign(id),
+                    None => Err(format!("unknown message: {}", line)),
+                };
+                if let Err(e) = reply {
+                    self.send(id, &ServerMessage::Error(e));
+                }
+            }
+            Event::Disconnected(id) => self.drop_client(id),
+        }
+        // Finishing a game can drop more clients.
+        while let Some((id, seat)) = self.dropped.pop() {
+            self.leave(id, seat, "disconnected");
+        }
+    }
+}
+
+fn main() {
+    let address = ::std::env::args().nth(1).unwrap_or(DEFAULT_ADDRESS.to_string());
+    let listener = match TcpListener::bind(&address[..]) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("couldn't listen on {}: {}\n{}", address, e, USAGE);
+            ::std::process::exit(1);
+        }
+    };
+
+    let (s, r) = channel();
+    thread::spawn(move || {
+        for (id, stream) in listener.incoming().enumerate() {
+            if let Ok(stream) = stream {
+                let s = s.clone();
+                thread::spawn(move || handle_connection(id, stream, s));
+            }
+        }
+    });
+
+    let mut server = Server {
+        clients: HashMap::new(),
+        games: vec![],
+        dropped: vec![],
+    };
+    for event in r {
+        server.handle(event);
+    }
+}
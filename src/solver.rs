@@ -0,0 +1,535 @@
+//! Exact solving of late positions, and endgame tablebases built from it.
+//!
+//! `Solver` searches every continuation of a position to the end of the
+//! game, remembering each position it solves under a canonical `Key`, so a
+//! position and its images under the board's symmetries are solved once.
+//! A `Tablebase` holds the positions a solver met with at most a given
+//! number of empty cells.  It's a cache rather than a complete table: only
+//! the continuations of the positions it was generated from are in it, and
+//! not even all of those, as the solver stops at a win on the spot.  An
+//! `Endgame` answers for every position within the table's limit, solving
+//! the ones the table misses.  Tables are sorted by key and saved in a
+//! compact binary file:
+//!
+//! ```text
+//! "HXTB" version:u8 radius:u8 max_empty:u8 count:u32
+//! count entries of key:16 bytes value:u8
+//! ```
+//!
+//! Numbers are little endian.  Values are from the side to move's point of
+//! view: 0 is a tie, 1 to 127 a win in that many plies and 128 plus n a
+//! loss in n plies.
+//!
+//! For positions too early to solve outright, `forced_win` and
+//! `winning_moves` search a few plies for a win that can't be stopped.
+//!
+//! Keys cover the stones and the side to move, not the swap rule, which
+//! only matters while there's a single stone on the board.  They need two
+//! bits a cell, so boards larger than the standard one can't be keyed.
+
+use super::*;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use symmetry::Symmetry;
+
+const MAGIC: &'static [u8] = b"HXTB";
+const VERSION: u8 = 1;
+
+/// The result of perfect play for the side to move, and how many plies it
+/// takes: the winner wins as fast as they can, the loser holds out as long
+/// as they can.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Value {
+    Win(u8),
+    Loss(u8),
+    Tie,
+}
+
+impl Value {
+    fn score(&self) -> i32 {
+        match *self {
+            Value::Win(n) => 1000 - n as i32,
+            Value::Loss(n) => -1000 + n as i32,
+            Value::Tie => 0,
+        }
+    }
+
+    pub fn is_better_than(&self, other: &Value) -> bool {
+        self.score() > other.score()
+    }
+
+    /// The value one ply earlier, for the player who moved into this
+    /// position.
+    pub fn back(&self) -> Value {
+        match *self {
+            Value::Win(n) => Value::Loss(n + 1),
+            Value::Loss(n) => Value::Win(n + 1),
+            Value::Tie => Value::Tie,
+        }
+    }
+
+    /// The value of a finished game for the side to move.
+    pub fn of_result(state: &GameState) -> Option<Value> {
+        match state.is_over() {
+            MoveResult::Good => None,
+            result => Some(match result.winner() {
+                Some(p) if p == state.current_player() => Value::Win(0),
+                Some(_) => Value::Loss(0),
+                None => Value::Tie,
+            }),
+        }
+    }
+
+    fn to_byte(&self) -> u8 {
+        match *self {
+            Value::Tie => 0,
+            Value::Win(n) => n,
+            Value::Loss(n) => 128 + n,
+        }
+    }
+
+    fn from_byte(b: u8) -> Value {
+        if b == 0 {
+            Value::Tie
+        } else if b < 128 {
+            Value::Win(b)
+        } else {
+            Value::Loss(b - 128)
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Win(n) => write!(f, "win in {}", n),
+            Value::Loss(n) => write!(f, "loss in {}", n),
+            Value::Tie => write!(f, "tie"),
+        }
+    }
+}
+
+/// A position's stones and side to move, two bits a cell.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Key(pub u64, pub u64);
+
+impl Key {
+    fn set(&mut self, bit: usize) {
+        if bit < 64 {
+            self.1 |= 1 << bit;
+        } else {
+            self.0 |= 1 << (bit - 64);
+        }
+    }
+}
+
+/// Works out canonical keys for one board size.
+#[derive(Clone)]
+pub struct Keys {
+    radius: u32,
+    cells: Vec<HexPosition>,
+    // For each symmetry, where each cell goes.
+    images: Vec<Vec<usize>>,
+}
+
+impl Keys {
+    pub fn new(radius: u32) -> Result<Keys, String> {
+        let cells: Vec<_> = HexGrid::new(radius).iter().collect();
+        if 2 * cells.len() + 1 > 128 {
+            return Err(format!("a board of radius {} is too large to key", radius));
+        }
+        let mut sorted: Vec<_> = cells.iter().cloned().zip(0 ..).collect();
+        sorted.sort_by(|a, b| a.0.cmp(&b.0));
+        let index = |pos: &HexPosition| sorted.binary_search_by(|probe| probe.0.cmp(pos)).map(|i| sorted[i].1).unwrap();
+
+        let images = Symmetry::all().iter().map(|symmetry| {
+            cells.iter().map(|pos| index(&symmetry.apply(pos))).collect()
+        }).collect();
+        Ok(Keys {
+            radius: radius,
+            cells: cells,
+            images: images,
+        })
+    }
+
+    pub fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    /// The smallest key of `state` under any symmetry.
+    pub fn key(&self, state: &GameState) -> Key {
+        let stones: Vec<_> = self.cells.iter().enumerate().filter_map(|(i, pos)| {
+            state.map().get(pos).map(|&p| (i, p))
+        }).collect();
+        let side = 2 * self.cells.len();
+
+        self.images.iter().map(|image| {
+            let mut key = Key(0, 0);
+            for &(i, player) in &stones {
+                key.set(2 * image[i] + if player == Player::Red { 0 } else { 1 });
+            }
+            if state.current_player() == Player::Green {
+                key.set(side);
+            }
+            key
+        }).min().unwrap()
+    }
+}
+
+/// The number of empty cells on the board.
+pub fn empty_cells(state: &GameState) -> usize {
+    state.map().grid().iter().filter(|pos| !state.map().contains(pos)).count()
+}
+
+/// Searches positions to the end, remembering every position solved.
+pub struct Solver {
+    keys: Keys,
+    table: HashMap<Key, Value>,
+    /// How many positions have been searched, counting repeats.
+    pub nodes: u64,
+}
+
+impl Solver {
+    pub fn new(radius: u32) -> Result<Solver, String> {
+        Ok(Solver {
+            keys: Keys::new(radius)?,
+            table: HashMap::new(),
+            nodes: 0,
+        })
+    }
+
+    /// A solver that already knows the positions in `tablebase`.
+    pub fn from_tablebase(tablebase: &Tablebase) -> Solver {
+        Solver {
+            keys: tablebase.keys.clone(),
+            table: tablebase.entries.iter().cloned().collect(),
+            nodes: 0,
+        }
+    }
+
+    /// The value of `state` for the side to move.  This visits every
+    /// continuation, so keep to positions with few empty cells.
+    pub fn solve(&mut self, state: &GameState) -> Value {
+        self.nodes += 1;
+        if let Some(value) = Value::of_result(state) {
+            return value;
+        }
+        let key = self.keys.key(state);
+        if let Some(&value) = self.table.get(&key) {
+            return value;
+        }
+
+        let mut best = None;
+        for mv in state.legal_moves() {
+            let value = self.solve(&state.with_move(&mv)).back();
+            if best.map(|b: Value| value.is_better_than(&b)).unwrap_or(true) {
+                best = Some(value);
+            }
+            // Nothing beats winning on the spot.
+            if value == Value::Win(1) {
+                break;
+            }
+        }
+        let value = best.unwrap();
+        self.table.insert(key, value);
+        value
+    }
+
+    /// The best move in `state` and its value, or `None` if the game is
+    /// over.
+    pub fn best_move(&mut self, state: &GameState) -> Option<(HexPosition, Value)> {
+        if Value::of_result(state).is_some() {
+            return None;
+        }
+        let mut best: Option<(HexPosition, Value)> = None;
+        for mv in state.legal_moves() {
+            let value = self.solve(&state.with_move(&mv)).back();
+            if best.map(|(_, b)| value.is_better_than(&b)).unwrap_or(true) {
+                best = Some((mv, value));
+            }
+        }
+        best
+    }
+
+    /// Every solved position so far with at most `max_empty` empty cells.
+    pub fn tablebase(&self, max_empty: usize) -> Tablebase {
+        let side = 2 * self.keys.cells.len();
+        let mut entries: Vec<_> = self.table.iter()
+            .filter(|&(key, _)| self.keys.cells.len() - stones(key, side) <= max_empty)
+            .map(|(&key, &value)| (key, value))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Tablebase {
+            keys: self.keys.clone(),
+            max_empty: max_empty,
+            entries: entries,
+        }
+    }
+}
+
+// The number of stones in a key, leaving out the side to move.
+fn stones(key: &Key, side: usize) -> usize {
+    let count = key.0.count_ones() as usize + key.1.count_ones() as usize;
+    let mut side_key = Key(0, 0);
+    side_key.set(side);
+    if (key.0 & side_key.0) | (key.1 & side_key.1) != 0 { count - 1 } else { count }
+}
+
+/// Whether the side to move can force a win within `plies` plies, their
+/// own moves and the replies counted together.  Unlike `Solver::solve`
+/// this looks only a few moves ahead, so it works in any position.
+pub fn forced_win(state: &GameState, plies: usize) -> bool {
+    !winning_moves(state, plies, true).is_empty()
+}
+
+/// The moves that force a win within `plies` plies, or just the first one
+/// found if `first` is set.
+pub fn winning_moves(state: &GameState, plies: usize, first: bool) -> Vec<HexPosition> {
+    let mut out = vec![];
+    if plies == 0 || state.is_over() != MoveResult::Good {
+        return out;
+    }
+    for mv in state.legal_moves() {
+        // Four in a row can be seen without making the move, which saves
+        // copying the board at the leaves of the search.
+        let wins = if !state.is_swap(&mv) && makes_four(state, &mv) {
+            true
+        } else if plies >= 3 {
+            let next = state.with_move(&mv);
+            next.is_over() == MoveResult::Good && forced_loss(&next, plies - 1)
+        } else {
+            false
+        };
+        if wins {
+            out.push(mv);
+            if first {
+                break;
+            }
+        }
+    }
+    out
+}
+
+// Whether the side to move makes four or more in a row by playing on the
+// empty cell `pos`, which wins whatever else it does.
+fn makes_four(state: &GameState, pos: &HexPosition) -> bool {
+    let player = state.current_player();
+    (0 .. 3).any(|i| {
+        let (a, b) = pos.bidirectional_ray(i);
+        let a = a.skip(1).take_while(|p| state.map().get(p) == Some(&player)).count();
+        let b = b.skip(1).take_while(|p| state.map().get(p) == Some(&player)).count();
+        a + b + 1 >= 4
+    })
+}
+
+// Whether every move for the side to move loses within `plies` plies.
+fn forced_loss(state: &GameState, plies: usize) -> bool {
+    let player = state.current_player();
+    state.legal_moves().into_iter().all(|mv| {
+        let next = state.with_move(&mv);
+        match next.is_over() {
+            MoveResult::End(p) => p != player,
+            MoveResult::Good => forced_win(&next, plies - 1),
+            _ => false,
+        }
+    })
+}
+
+/// Plays random moves that don't end the game until at most `empty` cells
+/// are left, for positions to solve.  Gives up and returns `None` if every
+/// move ends the game first.
+pub fn late_position<R: Rng>(config: Config, empty: usize, rng: &mut R) -> Option<GameState> {
+    let mut state = GameState::with_config(config);
+    while empty_cells(&state) > empty {
+        let moves: Vec<_> = state.legal_moves().into_iter()
+            .filter(|mv| !state.is_swap(mv) && state.with_move(mv).is_over() == MoveResult::Good)
+            .collect();
+        match rng.choose(&moves) {
+            Some(mv) => state.make_move(mv),
+            None => return None,
+        };
+    }
+    Some(state)
+}
+
+/// Solved positions, looked up by canonical key.
+pub struct Tablebase {
+    keys: Keys,
+    max_empty: usize,
+    entries: Vec<(Key, Value)>,
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0, |n, &b| n << 8 | b as u32)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |n, &b| n << 8 | b as u64)
+}
+
+fn write_u64<W: Write>(w: &mut W, n: u64) -> ::std::io::Result<()> {
+    let bytes: Vec<_> = (0 .. 8).map(|i| (n >> (8 * i)) as u8).collect();
+    w.write_all(&bytes)
+}
+
+impl Tablebase {
+    pub fn radius(&self) -> u32 {
+        self.keys.radius
+    }
+
+    /// The most empty cells a position in the table may have.
+    pub fn max_empty(&self) -> usize {
+        self.max_empty
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The value of `state` for the side to move, if the game is over or
+    /// the position is in the table.
+    pub fn probe(&self, state: &GameState) -> Option<Value> {
+        if let Some(value) = Value::of_result(state) {
+            return Some(value);
+        }
+        if state.config().radius != self.keys.radius || empty_cells(state) > self.max_empty {
+            return None;
+        }
+        let key = self.keys.key(state);
+        self.entries.binary_search_by(|probe| probe.0.cmp(&key)).ok().map(|i| self.entries[i].1)
+    }
+
+    /// The best move in `state` and its value, if the table knows it.
+    pub fn best_move(&self, state: &GameState) -> Option<(HexPosition, Value)> {
+        if self.probe(state).is_none() || Value::of_result(state).is_some() {
+            return None;
+        }
+        let mut best: Option<(HexPosition, Value)> = None;
+        let mut missing = false;
+        for mv in state.legal_moves() {
+            match self.probe(&state.with_move(&mv)) {
+                Some(value) => {
+                    let value = value.back();
+                    if best.map(|(_, b)| value.is_better_than(&b)).unwrap_or(true) {
+                        best = Some((mv, value));
+                    }
+                }
+                None => missing = true,
+            }
+        }
+        // The solver stops looking once it finds a win on the spot, so
+        // only then may other moves be missing.
+        match best {
+            Some((_, Value::Win(1))) => best,
+            _ if missing => None,
+            _ => best,
+        }
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<Tablebase, String> {
+        let mut header = [0; 11];
+        reader.read_exact(&mut header).map_err(|e| format!("couldn't read header: {}", e))?;
+        if &header[.. 4] != MAGIC {
+            return Err("not a tablebase".to_string());
+        }
+        if header[4] != VERSION {
+            return Err(format!("unknown tablebase version {}", header[4]));
+        }
+        let keys = Keys::new(header[5] as u32)?;
+        let max_empty = header[6] as usize;
+        let count = read_u32(&header[7 ..]) as usize;
+
+        let mut entries = Vec::with_capacity(count);
+        let mut entry = [0; 17];
+        for _ in 0 .. count {
+            reader.read_exact(&mut entry).map_err(|e| format!("couldn't read entry: {}", e))?;
+            let key = Key(read_u64(&entry[.. 8]), read_u64(&entry[8 .. 16]));
+            entries.push((key, Value::from_byte(entry[16])));
+        }
+        Ok(Tablebase {
+            keys: keys,
+            max_empty: max_empty,
+            entries: entries,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> ::std::io::Result<()> {
+        writer.write_all(MAGIC)?;
+        let count = self.entries.len() as u32;
+        writer.write_all(&[VERSION, self.keys.radius as u8, self.max_empty as u8])?;
+        writer.write_all(&[count as u8, (count >> 8) as u8, (count >> 16) as u8, (count >> 24) as u8])?;
+        for &(key, value) in &self.entries {
+            write_u64(&mut writer, key.0)?;
+            write_u64(&mut writer, key.1)?;
+            writer.write_all(&[value.to_byte()])?;
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> Result<Tablebase, String> {
+        let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+        Tablebase::read(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        File::create(path)
+            .and_then(|f| self.write(BufWriter::new(f)))
+            .map_err(|e| format!("couldn't write {}: {}", path, e))
+    }
+}
+
+/// The exact value of any position with at most a tablebase's `max_empty`
+/// empty cells.  Positions the table holds are looked up; the rest are
+/// solved when first asked for, starting from what the table knows, and
+/// remembered from then on.
+pub struct Endgame {
+    solver: Solver,
+    max_empty: usize,
+}
+
+impl Endgame {
+    pub fn new(tablebase: &Tablebase) -> Endgame {
+        Endgame {
+            solver: Solver::from_tablebase(tablebase),
+            max_empty: tablebase.max_empty,
+        }
+    }
+
+    pub fn max_empty(&self) -> usize {
+        self.max_empty
+    }
+
+    /// Whether `state` is one `probe` answers for: on the table's board,
+    /// with at most `max_empty` empty cells.
+    pub fn covers(&self, state: &GameState) -> bool {
+        state.config().radius == self.solver.keys.radius && empty_cells(state) <= self.max_empty
+    }
+
+    /// The value of `state` for the side to move, if the game is over or
+    /// the position is covered.
+    pub fn probe(&mut self, state: &GameState) -> Option<Value> {
+        if let Some(value) = Value::of_result(state) {
+            return Some(value);
+        }
+        if !self.covers(state) {
+            return None;
+        }
+        Some(self.solver.solve(state))
+    }
+
+    /// The best move in `state` and its value, if the position is covered
+    /// and the game isn't over.
+    pub fn best_move(&mut self, state: &GameState) -> Option<(HexPosition, Value)> {
+        if !self.covers(state) {
+            return None;
+        }
+        self.solver.best_move(state)
+    }
+}
@@ -0,0 +1,169 @@
+//! Test suites of positions with known right and wrong moves, for checking
+//! that changes to the AIs don't make them weaker tactically.
+//!
+//! Suites are EPD-style text files with one position a line: the four
+//! fields of a position string followed by `;` terminated operations.
+//!
+//! ```text
+//! 5/6/r6/rr2gg2/1r4g2/5rr1/2gg2r/2g3/5 g 4 f7 bm e6; id "win2-001";
+//! rrggr/rgrrgg/grggrrg/g1grggrr/rgrgrrggr/g1rrgrrg/grg1rg1/1rrggr/1gr1g r 4 i6 bm i5; am g6 g9 i8; id "trap-026";
+//! ```
+//!
+//! `bm` lists the best moves, one of which must be played, `am` lists moves
+//! to avoid, and `id` names the position.  Blank lines and lines starting
+//! with `#` are skipped.  The suites in `suites/` are made from puzzles
+//! with `puzzles epd`.
+
+use super::*;
+use ai::Ai;
+use puzzle::{Puzzle, PuzzleKind};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct SuiteEntry {
+    pub id: String,
+    pub state: GameState,
+    pub best: Vec<HexPosition>,
+    pub avoid: Vec<HexPosition>,
+}
+
+impl SuiteEntry {
+    pub fn parse(line: &str) -> Result<SuiteEntry, String> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        if words.len() < 4 {
+            return Err(format!("expected a position: {}", line));
+        }
+        let state = GameState::from_notation(&words[.. 4].join(" ")).map_err(|e| e.to_string())?;
+        let ops = words[4 ..].join(" ");
+        let mut entry = SuiteEntry {
+            id: String::new(),
+            state: state,
+            best: vec![],
+            avoid: vec![],
+        };
+
+        for op in ops.split(';').map(str::trim).filter(|op| !op.is_empty()) {
+            let mut words = op.splitn(2, ' ');
+            let (name, args) = (words.next().unwrap(), words.next().unwrap_or("").trim());
+            match name {
+                "bm" | "am" => {
+                    let moves = args.split_whitespace()
+                        .map(|m| entry.state.parse_move(m).map_err(|e| e.to_string()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if name == "bm" {
+                        entry.best = moves;
+                    } else {
+                        entry.avoid = moves;
+                    }
+                }
+                "id" => entry.id = args.trim_matches('"').to_string(),
+                // Other operations are for other tools.
+                _ => {}
+            }
+        }
+        if entry.best.is_empty() && entry.avoid.is_empty() {
+            return Err(format!("no bm or am: {}", line));
+        }
+        Ok(entry)
+    }
+
+    /// An entry whose best move is the puzzle's solution.  Trap puzzles
+    /// also list the moves that walk into the trap.
+    pub fn from_puzzle(puzzle: &Puzzle, id: &str) -> SuiteEntry {
+        let avoid = match puzzle.kind {
+            PuzzleKind::AvoidTrap => puzzle.traps(),
+            PuzzleKind::WinIn(_) => vec![],
+        };
+        SuiteEntry {
+            id: id.to_string(),
+            state: puzzle.state.clone(),
+            best: vec![puzzle.solution],
+            avoid: avoid,
+        }
+    }
+
+    pub fn passes(&self, mv: &HexPosition) -> bool {
+        (self.best.is_empty() || self.best.contains(mv)) && !self.avoid.contains(mv)
+    }
+
+    /// The entry's group, which is its id up to the first `-`.
+    pub fn group(&self) -> &str {
+        self.id.split('-').next().unwrap()
+    }
+}
+
+impl fmt::Display for SuiteEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = |moves: &[HexPosition]| -> Vec<String> {
+            moves.iter().map(|&mv| self.state.move_name(mv)).collect()
+        };
+        write!(f, "{}", self.state.to_notation())?;
+        if !self.best.is_empty() {
+            write!(f, " bm {};", names(&self.best).join(" "))?;
+        }
+        if !self.avoid.is_empty() {
+            write!(f, " am {};", names(&self.avoid).join(" "))?;
+        }
+        write!(f, " id \"{}\";", self.id)
+    }
+}
+
+/// Reads a suite file.
+pub fn load(path: &str) -> Result<Vec<SuiteEntry>, String> {
+    let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+    let mut entries = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("couldn't read {}: {}", path, e))?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(SuiteEntry::parse(&line).map_err(|e| format!("{}:{}: {}", path, i + 1, e))?);
+    }
+    Ok(entries)
+}
+
+/// How an AI did on a suite.
+pub struct Report {
+    pub passed: usize,
+    pub total: usize,
+    /// The ids of the failed entries and the moves played instead.
+    pub failures: Vec<(String, String)>,
+}
+
+impl Report {
+    pub fn pass_rate(&self) -> f32 {
+        self.passed as f32 / ::std::cmp::max(self.total, 1) as f32
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{} passed ({:.1}%)", self.passed, self.total, self.pass_rate() * 100.0)
+    }
+}
+
+/// Asks `ai` for a move in every position, with `budget` per move if
+/// given.
+pub fn run(ai: &mut dyn Ai, entries: &[SuiteEntry], budget: Option<Duration>) -> Report {
+    let mut report = Report {
+        passed: 0,
+        total: entries.len(),
+        failures: vec![],
+    };
+    for entry in entries {
+        let player = entry.state.current_player();
+        let mv = match budget {
+            Some(budget) => ai.choose_timed(&entry.state, player, budget),
+            None => ai.choose(&entry.state, player),
+        };
+        if entry.passes(&mv) {
+            report.passed += 1;
+        } else {
+            report.failures.push((entry.id.clone(), entry.state.move_name(mv)));
+        }
+    }
+    report
+}
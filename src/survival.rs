@@ -3,18 +3,21 @@ extern crate hexgame;
 extern crate rand;
 extern crate pbr;
 
-use hexgame::{GameState, Player, MoveResult};
+use hexgame::{Action, GameState, Player, MoveResult};
 use hexgame::ai::*;
+use hexgame::net::Broadcaster;
+use hexgame::record::GameRecord;
 
 use std::cell::RefCell;
+use std::time::Instant;
 
 type FeatureRankerAi = RankerAi<FeatureRanker>;
 
-#[derive(Debug)]
-enum GameResult {
-    Tie,
-    Player1,
-    Player2,
+// Games only need playing out until search proves who wins.
+fn contender(ranker: FeatureRanker, rec_depth: usize) -> FeatureRankerAi {
+    let mut ai = RankerAi::new(ranker, rec_depth);
+    ai.resign_below = Some(::std::f32::NEG_INFINITY);
+    ai
 }
 
 fn random_ai(rec_depth: usize) -> FeatureRankerAi {
@@ -27,7 +30,7 @@ fn random_ai(rec_depth: usize) -> FeatureRankerAi {
 
     ranker.normalize();
 
-    RankerAi::new(ranker, rec_depth)
+    contender(ranker, rec_depth)
 }
 
 fn breed(a: &FeatureRanker, b: &FeatureRanker) -> FeatureRanker {
@@ -54,34 +57,53 @@ fn randomize(a: &FeatureRanker) -> FeatureRanker {
     new
 }
 
-fn play_game(a: &mut FeatureRankerAi, b: &mut FeatureRankerAi) -> GameResult {
+fn play_game(a: &mut FeatureRankerAi, b: &mut FeatureRankerAi, broadcast: &mut Option<Broadcaster>) -> GameRecord {
     let mut game = GameState::new();
+    let mut record = GameRecord::new(format!("{:?}", a), format!("{:?}", b), game.config());
+    if let Some(ref mut broadcast) = *broadcast {
+        broadcast.start(&record.red, &record.green);
+    }
     let mut toggle = true;
     loop {
-        let next = if toggle {
-            a.choose(&game, game.current_player())
+        let start = Instant::now();
+        let action = if toggle {
+            a.act(&game, game.current_player())
         } else {
-            b.choose(&game, game.current_player())
+            b.act(&game, game.current_player())
         };
-        let (q, r) = next.as_axial();
-        println!("{},{}", q, r);
-        match game.make_move(&next) {
+        if let Action::Place(next) = action {
+            record.push(next, start.elapsed());
+        }
+        if let Some(ref mut broadcast) = *broadcast {
+            match action {
+                Action::Place(next) => broadcast.play(&game, next),
+                Action::Resign => broadcast.resign(),
+                _ => {}
+            }
+        }
+        match game.act(action) {
             MoveResult::Good => {},
-            MoveResult::Bad => panic!("bad move"),
-            MoveResult::End(Player::Red) => return GameResult::Player1,
-            MoveResult::End(Player::Green) => return GameResult::Player2,
-            MoveResult::Tie => return GameResult::Tie,
+            MoveResult::Bad => match action {
+                Action::Place(next) => panic!("bad move {}\n{:?}", game.cell(next), game),
+                _ => panic!("bad action\n{:?}", game),
+            },
+            result => {
+                record.result = Some(result);
+                println!("{}", record);
+                return record;
+            }
         }
 
         toggle = !toggle;
     }
 }
 
-fn round(round_id: u32, a: FeatureRankerAi, b: FeatureRankerAi, rec_depth: usize) -> (FeatureRankerAi, FeatureRankerAi) {
-    let c = RankerAi::new(breed(&a.ranker, &b.ranker), rec_depth);
-    let d = RankerAi::new(breed(&b.ranker, &a.ranker), rec_depth);
-    let e = RankerAi::new(randomize(&a.ranker), rec_depth);
-    let f = RankerAi::new(randomize(&b.ranker), rec_depth);
+fn round(round_id: u32, a: FeatureRankerAi, b: FeatureRankerAi, rec_depth: usize,
+         broadcast: &mut Option<Broadcaster>) -> (FeatureRankerAi, FeatureRankerAi) {
+    let c = contender(breed(&a.ranker, &b.ranker), rec_depth);
+    let d = contender(breed(&b.ranker, &a.ranker), rec_depth);
+    let e = contender(randomize(&a.ranker), rec_depth);
+    let f = contender(randomize(&b.ranker), rec_depth);
     let g = random_ai(rec_depth);
     let h = random_ai(rec_depth);
 
@@ -97,32 +119,30 @@ fn round(round_id: u32, a: FeatureRankerAi, b: FeatureRankerAi, rec_depth: usize
                 let mut ai_i = ai_i.borrow_mut();
                 let mut ai_k = ai_k.borrow_mut();
 
-                println!("\nnew game");
-                match play_game(&mut *ai_i, &mut *ai_k) {
-                    GameResult::Player1 => {
+                match play_game(&mut *ai_i, &mut *ai_k, broadcast).result.and_then(|r| r.winner()) {
+                    Some(Player::Red) => {
                         outset[i].0 += 1;
                         outset[k].0 -= 1;
                     }
-                    GameResult::Player2 => {
+                    Some(Player::Green) => {
                         outset[i].0 -= 1;
                         outset[k].0 += 1;
                     }
-                    GameResult::Tie => {}
+                    _ => {}
                 }
                 pbr.inc();
 
                 // invert order
-                println!("new game");
-                match play_game(&mut *ai_k, &mut *ai_i) {
-                    GameResult::Player1 => {
+                match play_game(&mut *ai_k, &mut *ai_i, broadcast).result.and_then(|r| r.winner()) {
+                    Some(Player::Red) => {
                         outset[i].0 -= 1;
                         outset[k].0 += 1;
                     }
-                    GameResult::Player2 => {
+                    Some(Player::Green) => {
                         outset[i].0 += 1;
                         outset[k].0 -= 1;
                     }
-                    GameResult::Tie => {}
+                    _ => {}
                 }
                 pbr.inc();
             }
@@ -139,8 +159,33 @@ fn round(round_id: u32, a: FeatureRankerAi, b: FeatureRankerAi, rec_depth: usize
 }
 
 const REC_DEPTH: usize = 4;
+
+const USAGE: &'static str = "\
+usage: survival [--broadcast ADDRESS]
+
+--broadcast publishes every game to a `server` at ADDRESS as it is played,
+for watching with `vis --spectate`.";
+
 fn main() {
-    let mut a = RankerAi::new(
+    let mut broadcast = None;
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (&arg[..], args.next()) {
+            ("--broadcast", Some(address)) => match Broadcaster::connect(&address) {
+                Ok(b) => broadcast = Some(b),
+                Err(e) => {
+                    println!("couldn't connect to {}: {}", address, e);
+                    return;
+                }
+            },
+            _ => {
+                println!("{}", USAGE);
+                return;
+            }
+        }
+    }
+
+    let mut a = contender(
         FeatureRanker {
 		window_score: 1.1696554,
 		triad_score: -3.5195274,
@@ -148,7 +193,7 @@ fn main() {
 		double_score: 2.168134
         }, 4);
 
-    let mut b = RankerAi::new(
+    let mut b = contender(
         FeatureRanker {
 		window_score: 1.6561589,
 		triad_score: -4.713683,
@@ -157,7 +202,7 @@ fn main() {
         }, 4);
 
     for i in 0 .. {
-        let (ar, br) = round(i, a, b, REC_DEPTH);
+        let (ar, br) = round(i, a, b, REC_DEPTH, &mut broadcast);
         a = ar;
         b = br;
 
@@ -0,0 +1,64 @@
+//! The twelve symmetries of the board: the six rotations about the centre
+//! cell, each with or without a reflection.  The rules only care about
+//! lines of stones, so a position and its images under any symmetry are
+//! worth the same, which is what training data augmentation and tablebase
+//! keys rely on.
+
+use super::*;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Symmetry {
+    /// Sixths of a turn, applied after the reflection.
+    turns: u8,
+    reflect: bool,
+}
+
+impl Symmetry {
+    pub fn identity() -> Symmetry {
+        Symmetry { turns: 0, reflect: false }
+    }
+
+    /// Every symmetry, the identity first.
+    pub fn all() -> Vec<Symmetry> {
+        let mut out = vec![];
+        for &reflect in &[false, true] {
+            for turns in 0 .. 6 {
+                out.push(Symmetry { turns: turns, reflect: reflect });
+            }
+        }
+        out
+    }
+
+    /// The symmetry that undoes this one.
+    pub fn inverse(&self) -> Symmetry {
+        if self.reflect {
+            *self
+        } else {
+            Symmetry { turns: (6 - self.turns) % 6, reflect: false }
+        }
+    }
+
+    pub fn apply(&self, pos: &HexPosition) -> HexPosition {
+        let (mut q, mut r) = pos.as_axial();
+        if self.reflect {
+            ::std::mem::swap(&mut q, &mut r);
+        }
+        for _ in 0 .. self.turns {
+            let (q2, r2) = (-r, q + r);
+            q = q2;
+            r = r2;
+        }
+        HexPosition::from_axial(q, r)
+    }
+
+    /// The image of `state`, with every stone and the last move moved.
+    pub fn apply_state(&self, state: &GameState) -> GameState {
+        let mut image = state.clone();
+        image.map = Map::new(HexGrid::new(state.config.radius));
+        for (pos, &player) in state.map.iter() {
+            image.map.insert(&self.apply(&pos), player);
+        }
+        image.last_move = state.last_move.map(|pos| self.apply(&pos));
+        image
+    }
+}
@@ -0,0 +1,135 @@
+extern crate hexgame;
+extern crate pbr;
+
+use hexgame::{Config, GameState};
+use hexgame::record::GameRecord;
+use hexgame::solver::{self, Endgame, Solver, Tablebase};
+use hexgame::training;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+const USAGE: &'static str = "\
+usage: tablebase generate [--empty K] [--positions N] [--seed N]
+                          [--records RECORD_FILE]... OUTPUT
+       tablebase probe FILE POSITION
+       tablebase info FILE
+
+generate solves positions with at most K empty cells (default 12) and
+writes every position met along the way to the tablebase OUTPUT.  The
+positions come from N random games (default 100) played without ending until
+K cells are left, and from the records given.  If OUTPUT already exists,
+its positions are kept and added to.  The solver searches every
+continuation, so each extra empty cell makes generating slower.
+
+The table is a cache of what was solved, not every position with K empty
+cells.  Positions it misses are solved when they come up, which is quick
+near the end of the game but slow with many empty cells.
+
+probe prints the value of a position, written as in `hexgame::notation`,
+and the best move.  Wrap an ai spec as `tablebase:FILE:SPEC` to play
+perfectly from K empty cells on.";
+
+fn parse<T: ::std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    value.and_then(|v| v.parse().ok()).ok_or(format!("bad value for {}", flag))
+}
+
+fn generate(args: &[String]) -> Result<(), String> {
+    let mut empty = 12;
+    let mut positions = 100;
+    let mut seed = 1;
+    let mut records = vec![];
+    let mut output = None;
+
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--empty" => empty = parse(&arg, args.next())?,
+            "--positions" => positions = parse(&arg, args.next())?,
+            "--seed" => seed = parse(&arg, args.next())?,
+            "--records" => records.push(args.next().ok_or("missing record file".to_string())?),
+            _ if output.is_none() => output = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    let output = output.ok_or("missing OUTPUT".to_string())?;
+    let config = Config::standard();
+
+    let mut solver = if Path::new(&output).exists() {
+        let existing = Tablebase::load(&output)?;
+        if existing.radius() != config.radius || existing.max_empty() != empty {
+            return Err(format!("{} holds positions with up to {} empty cells on radius {}",
+                               output, existing.max_empty(), existing.radius()));
+        }
+        Solver::from_tablebase(&existing)
+    } else {
+        Solver::new(config.radius)?
+    };
+
+    let mut seeds: Vec<GameState> = vec![];
+    for path in &records {
+        let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+        for record in GameRecord::read(BufReader::new(file)) {
+            let record = record.map_err(|e| format!("{}: {}", path, e))?;
+            if record.config.radius != config.radius {
+                continue;
+            }
+            seeds.extend(record.states().into_iter().filter(|s| solver::empty_cells(s) <= empty));
+        }
+    }
+    let mut rng = training::seeded_rng(seed);
+    seeds.extend((0 .. positions).filter_map(|_| solver::late_position(config, empty, &mut rng)));
+
+    let mut pbr = pbr::ProgressBar::new(seeds.len() as u64);
+    for state in &seeds {
+        solver.solve(state);
+        pbr.inc();
+    }
+
+    let table = solver.tablebase(empty);
+    table.save(&output)?;
+    println!("\n{} positions, {} searched", table.len(), solver.nodes);
+    Ok(())
+}
+
+fn probe(args: &[String]) -> Result<(), String> {
+    if args.len() != 2 {
+        return Err("expected FILE POSITION".to_string());
+    }
+    let table = Tablebase::load(&args[0])?;
+    let state = GameState::from_notation(&args[1]).map_err(|e| e.to_string())?;
+    let mut endgame = Endgame::new(&table);
+    match (table.probe(&state), endgame.probe(&state)) {
+        (Some(value), _) => println!("{}", value),
+        (None, Some(value)) => println!("{} (solved, not in the table)", value),
+        (None, None) => println!("more than {} empty cells", table.max_empty()),
+    }
+    if let Some((mv, value)) = endgame.best_move(&state) {
+        println!("best move {} ({})", state.move_name(mv), value);
+    }
+    Ok(())
+}
+
+fn info(args: &[String]) -> Result<(), String> {
+    let path = args.get(0).ok_or("expected FILE".to_string())?;
+    let table = Tablebase::load(path)?;
+    println!("radius {}", table.radius());
+    println!("up to {} empty cells", table.max_empty());
+    println!("{} positions", table.len());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<_> = ::std::env::args().skip(1).collect();
+    let result = match args.get(0).map(|s| &s[..]) {
+        Some("generate") => generate(&args[1 ..]),
+        Some("probe") => probe(&args[1 ..]),
+        Some("info") => info(&args[1 ..]),
+        _ => Err("expected generate, probe or info".to_string()),
+    };
+    if let Err(e) = result {
+        println!("{}\n{}", e, USAGE);
+        ::std::process::exit(1);
+    }
+}
@@ -0,0 +1,86 @@
+extern crate hexgame;
+
+use hexgame::ai::from_spec;
+use hexgame::suite::{self, SuiteEntry};
+
+use std::time::Duration;
+
+const USAGE: &'static str = "\
+usage: tactics [--suite FILE] [--time MS] [--min PERCENT] [--verbose] [SPEC...]
+
+Runs each ai, given as in the play binary and ranker:4 if none are, on every
+position of the suite FILE (default suites/tactics.epd) and prints how many
+it got right, overall and for each group of ids.
+
+    --time MS       search each position for MS milliseconds instead of
+                    letting the ai decide
+    --min PERCENT   fail unless every ai passes at least PERCENT
+    --verbose       list the positions failed and the moves played";
+
+fn parse<T: ::std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    value.and_then(|v| v.parse().ok()).ok_or(format!("bad value for {}", flag))
+}
+
+fn run() -> Result<(), String> {
+    let mut path = "suites/tactics.epd".to_string();
+    let mut budget = None;
+    let mut min = None;
+    let mut verbose = false;
+    let mut specs = vec![];
+
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--suite" => path = args.next().ok_or("missing suite file".to_string())?,
+            "--time" => budget = Some(Duration::from_millis(parse(&arg, args.next())?)),
+            "--min" => min = Some(parse::<f32>(&arg, args.next())?),
+            "--verbose" => verbose = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown flag: {}", arg)),
+            _ => specs.push(arg),
+        }
+    }
+    if specs.is_empty() {
+        specs.push("ranker:4".to_string());
+    }
+
+    let entries = suite::load(&path)?;
+    let mut groups: Vec<String> = vec![];
+    for entry in &entries {
+        if !groups.iter().any(|g| g == entry.group()) {
+            groups.push(entry.group().to_string());
+        }
+    }
+
+    let mut below = vec![];
+    for spec in &specs {
+        let mut ai = from_spec(spec)?;
+        let report = suite::run(&mut *ai, &entries, budget);
+        println!("{}: {}", spec, report);
+        for group in &groups {
+            let failed = report.failures.iter().filter(|f| f.0.split('-').next() == Some(group)).count();
+            let total = entries.iter().filter(|e: &&SuiteEntry| e.group() == group).count();
+            println!("    {:8} {}/{}", group, total - failed, total);
+        }
+        if verbose {
+            for &(ref id, ref mv) in &report.failures {
+                println!("    failed {}, played {}", id, mv);
+            }
+        }
+        if let Some(min) = min {
+            if report.pass_rate() * 100.0 < min {
+                below.push(spec.clone());
+            }
+        }
+    }
+    if !below.is_empty() {
+        return Err(format!("below {}%: {}", min.unwrap(), below.join(", ")));
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        println!("{}\n{}", e, USAGE);
+        ::std::process::exit(1);
+    }
+}
@@ -0,0 +1,353 @@
+extern crate hexgame;
+
+use hexgame::{Config, GameState, Player, MoveResult};
+use hexgame::clock::{Clock, TimeControl};
+use hexgame::protocol::{RemoteEngine, RemoteError, colour_name};
+use hexgame::record::GameRecord;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::Duration;
+
+const USAGE: &'static str = "\
+usage: tournament [--swiss ROUNDS] [--games N] [--time SECS] [--swap] [--records FILE] ENGINE...
+
+Each ENGINE is a command line that starts an engine speaking the text
+protocol, e.g. \"./engine-old ranker:4\".  Quote it if it has arguments.
+
+    --swiss ROUNDS  play a Swiss tournament instead of a round robin
+    --games N       games per pairing in a round robin, colours alternating
+                    (default 2)
+    --time SECS     sudden death time per player per game (default 60)
+    --swap          play with the swap rule
+    --records FILE  append the game records to FILE
+
+Illegal moves, crashes and running out of time lose the game.";
+
+// Time allowed on top of an engine's clock for the protocol round trip.
+const GRACE_MS: u64 = 500;
+
+struct Options {
+    swiss: Option<usize>,
+    games: usize,
+    time: Duration,
+    swap: bool,
+    records: Option<String>,
+    engines: Vec<String>,
+}
+
+struct Entrant {
+    command: String,
+    engine: Option<RemoteEngine>,
+    score: f32,
+    reds: usize,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        swiss: None,
+        games: 2,
+        time: Duration::from_secs(60),
+        swap: false,
+        records: None,
+        engines: vec![],
+    };
+
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match &arg[..] {
+            "--swiss" => options.swiss = Some(value()?.parse().map_err(|_| "bad --swiss".to_string())?),
+            "--games" => options.games = value()?.parse().map_err(|_| "bad --games".to_string())?,
+            "--time" => options.time = Duration::from_secs(value()?.parse().map_err(|_| "bad --time".to_string())?),
+            "--swap" => options.swap = true,
+            "--records" => options.records = Some(value()?),
+            _ => options.engines.push(arg),
+        }
+    }
+
+    if options.engines.len() < 2 {
+        return Err("need at least two engines".to_string());
+    }
+    Ok(options)
+}
+
+fn millis(d: Duration) -> u64 {
+    d.as_secs() * 1000 + d.subsec_nanos() as u64 / 1_000_000
+}
+
+fn engine(entrant: &mut Entrant) -> Result<&mut RemoteEngine, String> {
+    if entrant.engine.is_none() {
+        let engine = RemoteEngine::spawn(&entrant.command)
+            .map_err(|e| format!("couldn't start {}: {}", entrant.command, e))?;
+        entrant.engine = Some(engine);
+    }
+    Ok(entrant.engine.as_mut().unwrap())
+}
+
+fn prepare(entrant: &mut Entrant, time: Duration, config: Config) -> Result<(), String> {
+    let result = engine(entrant).and_then(|engine| {
+        let commands = [
+            format!("boardsize {}", config.radius),
+            format!("swap_rule {}", if config.swap { "on" } else { "off" }),
+            format!("time_settings {} 0 0", time.as_secs()),
+        ];
+        for command in &commands {
+            engine.send(command, Some(Duration::from_secs(10))).map_err(|e| format!("{:?}", e))?;
+        }
+        Ok(())
+    });
+    if result.is_err() {
+        entrant.engine = None;
+    }
+    result
+}
+
+/// Plays one game, adjudicating failures as a loss for the engine at fault.
+fn play_game(red: &mut Entrant, green: &mut Entrant, time: Duration, config: Config) -> GameRecord {
+    let mut game = GameState::with_config(config);
+    let mut record = GameRecord::new(&red.command[..], &green.command[..], game.config());
+    let mut clock = Clock::new(TimeControl::SuddenDeath(time));
+
+    let forfeit = |record: &mut GameRecord, loser: Player, why: String| {
+        println!("  {} forfeits: {}", colour_name(loser), why);
+        record.result = Some(MoveResult::End(loser.inverse()));
+    };
+
+    if let Err(e) = prepare(red, time, config) {
+        forfeit(&mut record, Player::Red, e);
+        return record;
+    }
+    if let Err(e) = prepare(green, time, config) {
+        forfeit(&mut record, Player::Green, e);
+        return record;
+    }
+
+    loop {
+        let player = game.current_player();
+        let (mover, other) = match player {
+            Player::Red => (&mut *red, &mut *green),
+            Player::Green => (&mut *green, &mut *red),
+        };
+
+        let left = clock.remaining(player);
+        let response = match engine(mover) {
+            Ok(engine) => {
+                let _ = engine.send(&format!("time_left {} {} 0", colour_name(player), left.as_secs()),
+                                    Some(Duration::from_secs(10)));
+                clock.start(player);
+                let timeout = left + Duration::from_millis(GRACE_MS);
+                engine.send(&format!("genmove {}", colour_name(player)), Some(timeout))
+            }
+            Err(e) => {
+                forfeit(&mut record, player, e);
+                return record;
+            }
+        };
+        let elapsed = left - clock.remaining(player);
+        let flag = clock.stop();
+
+        let cell = match response {
+            Ok(cell) => cell,
+            Err(e) => {
+                // A stuck or crashed engine is started afresh for its next game.
+                mover.engine = None;
+                let why = match e {
+                    RemoteError::Timeout => {
+                        println!("  {} ran out of time, no move after {}ms", colour_name(player), millis(elapsed));
+                        record.result = Some(MoveResult::Flagged(player));
+                        return record;
+                    }
+                    RemoteError::Failed(text) => format!("genmove failed: {}", text),
+                    RemoteError::Disconnected => "disconnected".to_string(),
+                };
+                forfeit(&mut record, player, why);
+                return record;
+            }
+        };
+        if let Some(flag) = flag {
+            println!("  {} ran out of time after {}ms", colour_name(player), millis(elapsed));
+            record.result = Some(flag);
+            return record;
+        }
+
+        if cell == "resign" {
+            println!("  {} resigns", colour_name(player));
+            record.result = Some(MoveResult::Resigned(player));
+            return record;
+        }
+        let pos = match game.parse_move(&cell) {
+            Ok(pos) => pos,
+            Err(e) => {
+                forfeit(&mut record, player, e.to_string());
+                return record;
+            }
+        };
+        record.push(pos, elapsed);
+
+        let result = game.make_move(&pos);
+        if result == MoveResult::Bad {
+            forfeit(&mut record, player, format!("illegal move {}", cell));
+            return record;
+        }
+        if result != MoveResult::Good {
+            record.result = Some(result);
+            return record;
+        }
+
+        let play = format!("play {} {}", colour_name(player), cell);
+        let answer = engine(other).and_then(|engine| {
+            engine.send(&play, Some(Duration::from_secs(10))).map_err(|e| format!("{:?}", e))
+        });
+        if let Err(e) = answer {
+            other.engine = None;
+            forfeit(&mut record, player.inverse(), e);
+            return record;
+        }
+    }
+}
+
+fn round_robin(count: usize, games: usize) -> Vec<Vec<(usize, usize)>> {
+    let mut rounds = vec![];
+    for g in 0 .. games {
+        let mut round = vec![];
+        for a in 0 .. count {
+            for b in a + 1 .. count {
+                round.push(if g % 2 == 0 { (a, b) } else { (b, a) });
+            }
+        }
+        rounds.push(round);
+    }
+    rounds
+}
+
+// Pairs players with similar scores who haven't met yet.  The player who
+// has had Red less often gets Red.  With an odd count the lowest ranked
+// player without a bye sits out and scores a point.
+fn swiss_round(entrants: &mut [Entrant], met: &[Vec<bool>], byes: &mut Vec<bool>) -> Vec<(usize, usize)> {
+    let mut order: Vec<_> = (0 .. entrants.len()).collect();
+    order.sort_by(|&a, &b| entrants[b].score.partial_cmp(&entrants[a].score).unwrap());
+
+    if order.len() % 2 == 1 {
+        let pos = order.iter().rposition(|&p| !byes[p]).unwrap_or(order.len() - 1);
+        let bye = order.remove(pos);
+        byes[bye] = true;
+        entrants[bye].score += 1.0;
+        println!("{} has a bye", entrants[bye].command);
+    }
+
+    let mut pairs = vec![];
+    while !order.is_empty() {
+        let a = order.remove(0);
+        let partner = order.iter().position(|&b| !met[a][b]).unwrap_or(0);
+        let b = order.remove(partner);
+        if entrants[a].reds <= entrants[b].reds {
+            pairs.push((a, b));
+        } else {
+            pairs.push((b, a));
+        }
+    }
+    pairs
+}
+
+fn two<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+    assert!(a != b);
+    if a < b {
+        let (left, right) = items.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = items.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            ::std::process::exit(1);
+        }
+    };
+
+    let count = options.engines.len();
+    let mut entrants: Vec<_> = options.engines.iter().map(|command| Entrant {
+        command: command.clone(),
+        engine: None,
+        score: 0.0,
+        reds: 0,
+    }).collect();
+    // table[a][b] is a's score against b.
+    let mut table = vec![vec![0.0f32; count]; count];
+    let mut met = vec![vec![false; count]; count];
+    let mut byes = vec![false; count];
+
+    let mut records = options.records.as_ref().map(|path| {
+        OpenOptions::new().create(true).append(true).open(path)
+            .unwrap_or_else(|e| panic!("couldn't open {}: {}", path, e))
+    });
+
+    let rounds = match options.swiss {
+        Some(rounds) => rounds,
+        None => options.games,
+    };
+    let schedule = round_robin(count, options.games);
+
+    for round in 0 .. rounds {
+        let pairs = match options.swiss {
+            Some(_) => swiss_round(&mut entrants, &met, &mut byes),
+            None => schedule[round].clone(),
+        };
+
+        println!("round {}", round + 1);
+        for (a, b) in pairs {
+            let record = {
+                let (red, green) = two(&mut entrants, a, b);
+                let config = Config {
+                    swap: options.swap,
+                    .. Config::standard()
+                };
+                play_game(red, green, options.time, config)
+            };
+
+            let (score_a, score_b) = match record.result.and_then(|r| r.winner()) {
+                Some(Player::Red) => (1.0, 0.0),
+                Some(Player::Green) => (0.0, 1.0),
+                _ => (0.5, 0.5),
+            };
+            println!("  {} - {}: {} - {}", entrants[a].command, entrants[b].command, score_a, score_b);
+
+            table[a][b] += score_a;
+            table[b][a] += score_b;
+            entrants[a].score += score_a;
+            entrants[b].score += score_b;
+            entrants[a].reds += 1;
+            met[a][b] = true;
+            met[b][a] = true;
+
+            if let Some(ref mut file) = records {
+                write!(file, "{}", record).unwrap();
+            }
+        }
+    }
+
+    println!("\ncrosstable");
+    let mut order: Vec<_> = (0 .. count).collect();
+    order.sort_by(|&a, &b| entrants[b].score.partial_cmp(&entrants[a].score).unwrap());
+    print!("{:>4}", "");
+    for i in 0 .. count {
+        print!("{:>6}", i + 1);
+    }
+    println!("{:>8}", "total");
+    for &a in &order {
+        print!("{:>4}", a + 1);
+        for b in 0 .. count {
+            if a == b || !met[a][b] {
+                print!("{:>6}", "-");
+            } else {
+                print!("{:>6}", table[a][b]);
+            }
+        }
+        println!("{:>8}  {}", entrants[a].score, entrants[a].command);
+    }
+}
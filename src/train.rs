@@ -0,0 +1,327 @@
+extern crate hexgame;
+
+use hexgame::Config;
+use hexgame::ai::{from_spec, FeatureRanker, LinearRanker, MlpRanker, RankerAi, ZeroAi, ZeroNet};
+use hexgame::record::GameRecord;
+use hexgame::training::{self, FitOptions, MlpOptions, ReplayBuffer, TdOptions, ZeroOptions};
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Write};
+
+const USAGE: &'static str = "\
+usage: train generate [--games N] [--ai SPEC] [--random-moves K] [--seed N] OUTPUT
+       train fit [--epochs N] [--rate R] [--start WEIGHTS_FILE] RECORD_FILE...
+       train td [--games N] [--alpha A] [--lambda L] [--epsilon E] [--seed N]
+                [--checkpoint-every K] [--eval-games M] [--depth D]
+                [--start WEIGHTS_FILE] PREFIX
+       train mlp [--hidden H] [--epochs N] [--rate R] [--batch B] [--seed N]
+                 [--no-symmetry] [--start WEIGHTS_FILE] RECORD_FILE...
+       train zero [--rounds N] [--games G] [--iterations I] [--hidden H]
+                  [--buffer SIZE] [--steps S] [--batch B] [--rate R]
+                  [--temperature-moves K] [--eval-games M] [--depth D]
+                  [--start WEIGHTS_FILE] [--seed N] PREFIX
+
+generate plays N games (default 100) of the ai against itself, default
+ranker:2, and appends the records to OUTPUT.  Each game opens with K random
+moves (default 4), chosen reproducibly from the seed.
+
+fit labels every position in the records with the game's result and fits
+the feature weights to predict it by logistic regression, starting from
+WEIGHTS_FILE or the default weights.  The weights are printed in the
+format `ranker:DEPTH:WEIGHTS_FILE` reads.
+
+td trains a pattern evaluator by TD(lambda) over N games (default 10000)
+against itself, starting from WEIGHTS_FILE or all zeros.  Every K games
+(default 1000) the weights are saved to PREFIX-<games>.txt, for
+`linear:DEPTH:WEIGHTS_FILE`, and M games (default 20) are played at depth D
+(default 2) against the default ranker; the checkpoint's score is printed.
+
+mlp trains a neural network evaluator with H hidden units (default 32) on
+the same labelled positions as fit, each also seen under the twelve
+symmetries of the board unless --no-symmetry is given.  It starts from
+WEIGHTS_FILE or random weights, and prints the network in the format
+`mlp:DEPTH:WEIGHTS_FILE` reads.
+
+zero trains a policy and value network by self-play.  Each of N rounds
+(default 10) plays G games (default 20) of zero search with I iterations a
+move (default 200), the first K moves (default 8) drawn in proportion to the
+search's visits, and adds every position to a replay buffer holding the
+last SIZE positions (default 20000).  The network, with H hidden units
+(default 64) unless started from WEIGHTS_FILE, then takes S steps (default
+200) over minibatches of B positions (default 32).  Each round's network is
+saved to PREFIX-<round>.txt, for `zero:ITERATIONS:WEIGHTS_FILE`, and plays M
+games (default 10) against ranker:D (default 2).  The buffer is kept in
+PREFIX-buffer.txt, and picked up again if it's already there.";
+
+fn parse<T: ::std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    value.and_then(|v| v.parse().ok()).ok_or(format!("bad value for {}", flag))
+}
+
+fn generate(args: &[String]) -> Result<(), String> {
+    let mut games = 100;
+    let mut spec = "ranker:2".to_string();
+    let mut random_moves = 4;
+    let mut seed = 1;
+    let mut output = None;
+
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--games" => games = parse(&arg, args.next())?,
+            "--ai" => spec = args.next().ok_or("missing ai".to_string())?,
+            "--random-moves" => random_moves = parse(&arg, args.next())?,
+            "--seed" => seed = parse(&arg, args.next())?,
+            _ if output.is_none() => output = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    let output = output.ok_or("missing OUTPUT".to_string())?;
+
+    let mut red = from_spec(&spec)?;
+    let mut green = from_spec(&spec)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&output)
+        .map_err(|e| format!("couldn't open {}: {}", output, e))?;
+    let mut rng = training::seeded_rng(seed);
+
+    for i in 0 .. games {
+        let mut record = training::self_play(&mut *red, &mut *green, Config::standard(), random_moves, &mut rng);
+        record.red = spec.clone();
+        record.green = spec.clone();
+        write!(file, "{}", record).map_err(|e| format!("couldn't write {}: {}", output, e))?;
+        println!("game {}/{}: {} moves", i + 1, games, record.moves.len());
+    }
+    Ok(())
+}
+
+fn fit(args: &[String]) -> Result<(), String> {
+    let mut options = FitOptions::default();
+    let mut start = FeatureRanker::default();
+    let mut paths = vec![];
+
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--epochs" => options.epochs = parse(&arg, args.next())?,
+            "--rate" => options.learning_rate = parse(&arg, args.next())?,
+            "--start" => start = FeatureRanker::load(&args.next().ok_or("missing weights file".to_string())?)?,
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err("no record files".to_string());
+    }
+
+    let mut games = 0;
+    let mut samples = vec![];
+    for path in &paths {
+        let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+        for record in GameRecord::read(BufReader::new(file)) {
+            let record = record.map_err(|e| format!("{}: {}", path, e))?;
+            samples.extend(training::samples(&record));
+            games += 1;
+        }
+    }
+
+    let fitted = training::fit(&start, &samples, options);
+    println!("# {} positions from {} games", samples.len(), games);
+    println!("# log loss {} -> {}", training::loss(&start, &samples), training::loss(&fitted, &samples));
+    print!("{}", fitted);
+    Ok(())
+}
+
+fn td(args: &[String]) -> Result<(), String> {
+    let mut options = TdOptions::default();
+    let mut games = 10000;
+    let mut checkpoint_every = 1000;
+    let mut eval_games = 20;
+    let mut depth = 2;
+    let mut seed = 1;
+    let mut ranker = LinearRanker::new();
+    let mut prefix = None;
+
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--games" => games = parse(&arg, args.next())?,
+            "--alpha" => options.alpha = parse(&arg, args.next())?,
+            "--lambda" => options.lambda = parse(&arg, args.next())?,
+            "--epsilon" => options.epsilon = parse(&arg, args.next())?,
+            "--checkpoint-every" => checkpoint_every = parse(&arg, args.next())?,
+            "--eval-games" => eval_games = parse(&arg, args.next())?,
+            "--depth" => depth = parse(&arg, args.next())?,
+            "--seed" => seed = parse(&arg, args.next())?,
+            "--start" => ranker = LinearRanker::load(&args.next().ok_or("missing weights file".to_string())?)?,
+            _ if prefix.is_none() => prefix = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    let prefix = prefix.ok_or("missing PREFIX".to_string())?;
+    let checkpoint_every = ::std::cmp::max(checkpoint_every, 1);
+
+    let mut rng = training::seeded_rng(seed);
+    let mut baseline = RankerAi::new(FeatureRanker::default(), depth);
+    let mut moves = 0;
+
+    for i in 1 .. games + 1 {
+        moves += training::td_game(&mut ranker, Config::standard(), options, &mut rng).moves.len();
+        if i % checkpoint_every != 0 && i != games {
+            continue;
+        }
+
+        let path = format!("{}-{}.txt", prefix, i);
+        File::create(&path)
+            .and_then(|mut f| write!(f, "{}", ranker))
+            .map_err(|e| format!("couldn't write {}: {}", path, e))?;
+        let mut candidate = RankerAi::new(ranker.clone(), depth);
+        let score = training::evaluate(&mut candidate, &mut baseline, Config::standard(), eval_games, 2, &mut rng);
+        println!("{}: {} games, {} moves, scores {:.1}% against ranker:{}",
+                 path, i, moves, score * 100.0, depth);
+    }
+    Ok(())
+}
+
+fn mlp(args: &[String]) -> Result<(), String> {
+    let mut options = MlpOptions::default();
+    let mut hidden = 32;
+    let mut seed = 1;
+    let mut start = None;
+    let mut paths = vec![];
+
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--hidden" => hidden = parse(&arg, args.next())?,
+            "--epochs" => options.epochs = parse(&arg, args.next())?,
+            "--rate" => options.learning_rate = parse(&arg, args.next())?,
+            "--batch" => options.batch_size = parse(&arg, args.next())?,
+            "--seed" => seed = parse(&arg, args.next())?,
+            "--no-symmetry" => options.symmetries = false,
+            "--start" => start = Some(MlpRanker::load(&args.next().ok_or("missing weights file".to_string())?)?),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err("no record files".to_string());
+    }
+
+    let mut rng = training::seeded_rng(seed);
+    let mut net = match start {
+        Some(net) => net,
+        None => MlpRanker::random(Config::standard().radius, hidden, &mut rng),
+    };
+
+    let mut games = 0;
+    let mut samples = vec![];
+    for path in &paths {
+        let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+        for record in GameRecord::read(BufReader::new(file)) {
+            let record = record.map_err(|e| format!("{}: {}", path, e))?;
+            samples.extend(training::mlp_samples(&net, &record, options.symmetries));
+            games += 1;
+        }
+    }
+
+    let before = training::mlp_loss(&net, &samples);
+    training::fit_mlp(&mut net, &samples, options, &mut rng);
+    println!("# {} positions from {} games", samples.len(), games);
+    println!("# log loss {} -> {}", before, training::mlp_loss(&net, &samples));
+    print!("{}", net);
+    Ok(())
+}
+
+fn zero(args: &[String]) -> Result<(), String> {
+    let mut options = ZeroOptions::default();
+    let mut rounds = 10;
+    let mut games = 20;
+    let mut iterations = 200;
+    let mut hidden = 64;
+    let mut capacity = 20000;
+    let mut temperature_moves = 8;
+    let mut eval_games = 10;
+    let mut depth = 2;
+    let mut seed = 1;
+    let mut start = None;
+    let mut prefix = None;
+
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--rounds" => rounds = parse(&arg, args.next())?,
+            "--games" => games = parse(&arg, args.next())?,
+            "--iterations" => iterations = parse(&arg, args.next())?,
+            "--hidden" => hidden = parse(&arg, args.next())?,
+            "--buffer" => capacity = parse(&arg, args.next())?,
+            "--steps" => options.steps = parse(&arg, args.next())?,
+            "--batch" => options.batch_size = parse(&arg, args.next())?,
+            "--rate" => options.learning_rate = parse(&arg, args.next())?,
+            "--temperature-moves" => temperature_moves = parse(&arg, args.next())?,
+            "--eval-games" => eval_games = parse(&arg, args.next())?,
+            "--depth" => depth = parse(&arg, args.next())?,
+            "--seed" => seed = parse(&arg, args.next())?,
+            "--start" => start = Some(ZeroNet::load(&args.next().ok_or("missing weights file".to_string())?)?),
+            _ if prefix.is_none() => prefix = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    let prefix = prefix.ok_or("missing PREFIX".to_string())?;
+
+    let mut rng = training::seeded_rng(seed);
+    let net = match start {
+        Some(net) => net,
+        None => ZeroNet::random(Config::standard().radius, hidden, &mut rng),
+    };
+    let buffer_path = format!("{}-buffer.txt", prefix);
+    let mut buffer = if ::std::path::Path::new(&buffer_path).exists() {
+        ReplayBuffer::load(&buffer_path, capacity)?
+    } else {
+        ReplayBuffer::new(capacity)
+    };
+
+    let mut ai = ZeroAi::new(net, iterations);
+    ai.noise = Some((0.3, 0.25));
+    let mut baseline = RankerAi::new(FeatureRanker::default(), depth);
+
+    for round in 1 .. rounds + 1 {
+        for _ in 0 .. games {
+            let (_, entries) = training::zero_game(&ai, Config::standard(), temperature_moves, &mut rng);
+            for entry in entries {
+                buffer.push(entry);
+            }
+        }
+
+        let before = training::zero_loss(&ai.net, buffer.entries());
+        training::fit_zero(&mut ai.net, &buffer, options, &mut rng);
+        let after = training::zero_loss(&ai.net, buffer.entries());
+
+        let path = format!("{}-{}.txt", prefix, round);
+        File::create(&path)
+            .and_then(|mut f| write!(f, "{}", ai.net))
+            .map_err(|e| format!("couldn't write {}: {}", path, e))?;
+        buffer.save(&buffer_path)?;
+
+        println!("round {}: {} positions, value loss {} -> {}, policy loss {} -> {}",
+                 round, buffer.len(), before.0, after.0, before.1, after.1);
+        if eval_games > 0 {
+            let mut candidate = ZeroAi::new(ai.net.clone(), iterations);
+            let score = training::evaluate(&mut candidate, &mut baseline, Config::standard(), eval_games, 2, &mut rng);
+            println!("{} scores {:.1}% against ranker:{}", path, score * 100.0, depth);
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<_> = ::std::env::args().skip(1).collect();
+    let result = match args.get(0).map(|s| &s[..]) {
+        Some("generate") => generate(&args[1 ..]),
+        Some("fit") => fit(&args[1 ..]),
+        Some("td") => td(&args[1 ..]),
+        Some("mlp") => mlp(&args[1 ..]),
+        Some("zero") => zero(&args[1 ..]),
+        _ => Err("expected generate, fit, td, mlp or zero".to_string()),
+    };
+    if let Err(e) = result {
+        println!("{}\n{}", e, USAGE);
+        ::std::process::exit(1);
+    }
+}
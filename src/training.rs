@@ -0,0 +1,632 @@
+//! Fitting evaluator weights to the outcomes of self-play games.
+//!
+//! Every position of a game becomes a `Sample`: the `FeatureRanker`
+//! features from the point of view of the side to move, labelled with how
+//! the game turned out for that side.  `fit` then runs logistic regression
+//! over the samples, so that `sigmoid(rank(us) - rank(them))` predicts the
+//! result.  Those are the very scores `RankerAi` searches on, so the fitted
+//! weights drop straight into a `FeatureRanker`.
+//!
+//! `td_game` instead learns a `LinearRanker` by temporal-difference
+//! learning, TD(λ), as it plays itself, and `fit_mlp` trains an
+//! `MlpRanker` on the same kind of labelled positions as `fit`, each one
+//! also seen under every symmetry of the board.
+//!
+//! Finally `zero_game` and `fit_zero` make up an AlphaZero style loop:
+//! `ZeroAi` plays itself, every searched position goes into a
+//! `ReplayBuffer` with the search's visit counts and the game's result, and
+//! the `ZeroNet` is trained to predict both.
+
+use super::*;
+use ai::{Ai, FeatureRanker, LinearRanker, MlpRanker, ZeroAi, ZeroNet};
+use ai::layer::Layer;
+use rand::{Rng, SeedableRng, StdRng};
+use record::GameRecord;
+use symmetry::Symmetry;
+use std::collections::VecDeque;
+use std::f32::NEG_INFINITY;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::time::Instant;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Sample {
+    pub features: [f32; 4],
+    /// 1 for a win for the side to move, 0 for a loss and 0.5 otherwise.
+    pub outcome: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct FitOptions {
+    pub epochs: usize,
+    pub learning_rate: f32,
+    /// L2 penalty on the weights, to keep rarely seen features in check.
+    pub l2: f32,
+}
+
+impl Default for FitOptions {
+    fn default() -> FitOptions {
+        FitOptions {
+            epochs: 2000,
+            learning_rate: 0.05,
+            l2: 0.0001,
+        }
+    }
+}
+
+/// A generator for reproducible runs.
+pub fn seeded_rng(seed: usize) -> StdRng {
+    StdRng::from_seed(&[seed][..])
+}
+
+/// Plays one game between two AIs, opening with `random_moves` random
+/// moves so that repeated games differ.
+pub fn self_play<R: Rng>(red: &mut dyn Ai, green: &mut dyn Ai, config: Config,
+                         random_moves: usize, rng: &mut R) -> GameRecord {
+    let mut state = GameState::with_config(config);
+    let mut record = GameRecord::new("self-play", "self-play", config);
+    loop {
+        let player = state.current_player();
+        let start = Instant::now();
+        let pos = if record.moves.len() < random_moves {
+            let moves = state.legal_moves();
+            *rng.choose(&moves).unwrap()
+        } else {
+            match player {
+                Player::Red => red.choose(&state, player),
+                Player::Green => green.choose(&state, player),
+            }
+        };
+        record.push(pos, start.elapsed());
+        match state.make_move(&pos) {
+            MoveResult::Good => {}
+            result => {
+                record.result = Some(result);
+                return record;
+            }
+        }
+    }
+}
+
+/// Plays `games` games between `a` and `b`, with `a` taking Red in every
+/// other game, and returns `a`'s share of the points.
+pub fn evaluate<R: Rng>(a: &mut dyn Ai, b: &mut dyn Ai, config: Config, games: usize,
+                        random_moves: usize, rng: &mut R) -> f32 {
+    let mut points = 0.0;
+    for i in 0 .. games {
+        let (record, a_plays) = if i % 2 == 0 {
+            (self_play(a, b, config, random_moves, rng), Player::Red)
+        } else {
+            (self_play(b, a, config, random_moves, rng), Player::Green)
+        };
+        points += outcome(record.result, a_plays).unwrap_or(0.5);
+    }
+    points / games.max(1) as f32
+}
+
+fn outcome(result: Option<MoveResult>, player: Player) -> Option<f32> {
+    match result {
+        Some(result) => Some(match result.winner() {
+            Some(p) if p == player => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        }),
+        None => None,
+    }
+}
+
+/// The feature differences for `player` over their opponent.
+pub fn features(state: &GameState, player: Player) -> [f32; 4] {
+    let ours = FeatureRanker::features(state, player);
+    let theirs = FeatureRanker::features(state, player.inverse());
+    [ours[0] - theirs[0], ours[1] - theirs[1], ours[2] - theirs[2], ours[3] - theirs[3]]
+}
+
+/// Every undecided position of a finished game, labelled with its result.
+/// Unfinished games give nothing.
+pub fn samples(record: &GameRecord) -> Vec<Sample> {
+    let mut out = vec![];
+    for state in record.states() {
+        if state.is_over() != MoveResult::Good {
+            continue;
+        }
+        if let Some(outcome) = outcome(record.result, state.current_player()) {
+            out.push(Sample {
+                features: features(&state, state.current_player()),
+                outcome: outcome,
+            });
+        }
+    }
+    out
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn predict(weights: &[f32; 4], features: &[f32; 4]) -> f32 {
+    sigmoid(weights.iter().zip(features).map(|(w, x)| w * x).sum())
+}
+
+/// The mean log loss of `ranker`'s predictions over `samples`.
+pub fn loss(ranker: &FeatureRanker, samples: &[Sample]) -> f32 {
+    let weights = ranker.weights();
+    let total: f32 = samples.iter().map(|s| {
+        let p = predict(&weights, &s.features).max(1e-6).min(1.0 - 1e-6);
+        -(s.outcome * p.ln() + (1.0 - s.outcome) * (1.0 - p).ln())
+    }).sum();
+    total / samples.len().max(1) as f32
+}
+
+/// Logistic regression by full-batch gradient descent, starting from
+/// `start`.  The same samples always give the same weights.
+pub fn fit(start: &FeatureRanker, samples: &[Sample], options: FitOptions) -> FeatureRanker {
+    let mut weights = start.weights();
+    let n = samples.len().max(1) as f32;
+    for _ in 0 .. options.epochs {
+        let mut gradient = [0.0f32; 4];
+        for s in samples {
+            let error = predict(&weights, &s.features) - s.outcome;
+            for i in 0 .. 4 {
+                gradient[i] += error * s.features[i];
+            }
+        }
+        for i in 0 .. 4 {
+            weights[i] -= options.learning_rate * (gradient[i] / n + options.l2 * weights[i]);
+        }
+    }
+    FeatureRanker::from_weights(weights)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TdOptions {
+    pub alpha: f32,
+    pub lambda: f32,
+    /// The chance of playing a random move instead of the best one.
+    pub epsilon: f32,
+}
+
+impl Default for TdOptions {
+    fn default() -> TdOptions {
+        TdOptions {
+            alpha: 0.01,
+            lambda: 0.7,
+            epsilon: 0.1,
+        }
+    }
+}
+
+// Red's chance of winning as `ranker` sees it, and the features that went
+// into it.
+fn red_value(ranker: &mut LinearRanker, state: &GameState) -> (f32, Vec<f32>) {
+    let red = ranker.features(state, Player::Red);
+    let green = ranker.features(state, Player::Green);
+    let phi: Vec<_> = red.iter().zip(&green).map(|(r, g)| r - g).collect();
+    let value = sigmoid(ranker.weights.iter().zip(&phi).map(|(w, x)| w * x).sum());
+    (value, phi)
+}
+
+/// Plays one game of `ranker` against itself, looking one move ahead, and
+/// after every move nudges the weights so that the previous positions'
+/// values move toward the value of the new one, or toward the result once
+/// the game is over.
+pub fn td_game<R: Rng>(ranker: &mut LinearRanker, config: Config, options: TdOptions, rng: &mut R) -> GameRecord {
+    let mut state = GameState::with_config(config);
+    let mut record = GameRecord::new("td", "td", config);
+    let mut trace = vec![0.0f32; ranker.weights.len()];
+    let (mut value, mut phi) = red_value(ranker, &state);
+
+    loop {
+        let player = state.current_player();
+        let start = Instant::now();
+        let moves = state.legal_moves();
+        let pos = if rng.gen::<f32>() < options.epsilon {
+            *rng.choose(&moves).unwrap()
+        } else {
+            let mut best = (NEG_INFINITY, moves[0]);
+            for &mv in &moves {
+                let next = state.with_move(&mv);
+                let ours = match next.is_over() {
+                    MoveResult::Good => {
+                        let red = red_value(ranker, &next).0;
+                        if player == Player::Red { red } else { 1.0 - red }
+                    }
+                    result => outcome(Some(result), player).unwrap(),
+                };
+                if ours > best.0 {
+                    best = (ours, mv);
+                }
+            }
+            best.1
+        };
+        record.push(pos, start.elapsed());
+        let result = state.make_move(&pos);
+
+        let (target, next) = match result {
+            MoveResult::Good => {
+                let (v, p) = red_value(ranker, &state);
+                (v, Some((v, p)))
+            }
+            result => (outcome(Some(result), Player::Red).unwrap(), None),
+        };
+
+        let delta = target - value;
+        let slope = value * (1.0 - value);
+        for i in 0 .. trace.len() {
+            trace[i] = options.lambda * trace[i] + slope * phi[i];
+            ranker.weights[i] += options.alpha * delta * trace[i];
+        }
+
+        match next {
+            Some((v, p)) => {
+                value = v;
+                phi = p;
+            }
+            None => {
+                record.result = Some(result);
+                return record;
+            }
+        }
+    }
+}
+
+/// A position encoded for an `MlpRanker`, from the side to move.
+pub struct MlpSample {
+    pub input: Vec<f32>,
+    pub outcome: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct MlpOptions {
+    pub epochs: usize,
+    pub learning_rate: f32,
+    pub batch_size: usize,
+    /// Add the images of every position under the board's symmetries.
+    pub symmetries: bool,
+}
+
+impl Default for MlpOptions {
+    fn default() -> MlpOptions {
+        MlpOptions {
+            epochs: 20,
+            learning_rate: 0.01,
+            batch_size: 32,
+            symmetries: true,
+        }
+    }
+}
+
+/// Like `samples`, encoded for `net`.
+pub fn mlp_samples(net: &MlpRanker, record: &GameRecord, symmetries: bool) -> Vec<MlpSample> {
+    let symmetries = if symmetries { Symmetry::all() } else { vec![Symmetry::identity()] };
+    let mut out = vec![];
+    for state in record.states() {
+        if state.is_over() != MoveResult::Good {
+            continue;
+        }
+        if let Some(outcome) = outcome(record.result, state.current_player()) {
+            for symmetry in &symmetries {
+                out.push(MlpSample {
+                    input: net.encode(&symmetry.apply_state(&state), state.current_player()),
+                    outcome: outcome,
+                });
+            }
+        }
+    }
+    out
+}
+
+/// The mean log loss of `net`'s predictions over `samples`.
+pub fn mlp_loss(net: &MlpRanker, samples: &[MlpSample]) -> f32 {
+    let mut total = 0.0;
+    for batch in samples.chunks(256) {
+        let inputs: Vec<_> = batch.iter().map(|s| s.input.clone()).collect();
+        for (s, output) in batch.iter().zip(net.forward_batch(&inputs)) {
+            let p = sigmoid(output).max(1e-6).min(1.0 - 1e-6);
+            total += -(s.outcome * p.ln() + (1.0 - s.outcome) * (1.0 - p).ln());
+        }
+    }
+    total / samples.len().max(1) as f32
+}
+
+/// Trains `net` to predict the samples' outcomes by minibatch gradient
+/// descent on the log loss, visiting the samples in an order drawn from
+/// `rng`.
+pub fn fit_mlp<R: Rng>(net: &mut MlpRanker, samples: &[MlpSample], options: MlpOptions, rng: &mut R) {
+    let inputs = net.inputs();
+    let hidden = net.hidden();
+    let mut order: Vec<_> = (0 .. samples.len()).collect();
+
+    for _ in 0 .. options.epochs {
+        rng.shuffle(&mut order);
+        for batch in order.chunks(options.batch_size.max(1)) {
+            let mut hidden_grad = Layer::new(inputs, hidden);
+            let mut output_grad = Layer::new(hidden, 1);
+
+            for &i in batch {
+                let sample = &samples[i];
+                let a = net.forward(sample.input.clone());
+                let error = sigmoid(a.output) - sample.outcome;
+                output_grad.bias[0] += error;
+                for unit in 0 .. hidden {
+                    output_grad.weights[unit] += error * a.hidden[unit];
+                    let slope = error * net.output.weights[unit] * (1.0 - a.hidden[unit] * a.hidden[unit]);
+                    hidden_grad.bias[unit] += slope;
+                    for (j, &x) in a.input.iter().enumerate().filter(|&(_, &x)| x != 0.0) {
+                        hidden_grad.weights[unit * inputs + j] += slope * x;
+                    }
+                }
+            }
+
+            let step = options.learning_rate / batch.len() as f32;
+            net.hidden_layer.descend(&hidden_grad, step);
+            net.output.descend(&output_grad, step);
+        }
+    }
+}
+
+/// A position searched in self-play, with the share of the search's visits
+/// each move got and the result for the side to move: 1 for a win, -1 for a
+/// loss and 0 otherwise.
+#[derive(Clone)]
+pub struct ReplayEntry {
+    pub state: GameState,
+    pub visits: Vec<(HexPosition, f32)>,
+    pub outcome: f32,
+}
+
+impl ReplayEntry {
+    /// Reads a line written by this type's `Display` impl:
+    /// `POSITION | OUTCOME | CELL:SHARE ...`.
+    pub fn parse(line: &str) -> Result<ReplayEntry, String> {
+        let fields: Vec<_> = line.split('|').map(str::trim).collect();
+        if fields.len() != 3 {
+            return Err(format!("bad replay entry: {}", line));
+        }
+        let state = GameState::from_notation(fields[0]).map_err(|e| e.to_string())?;
+        let outcome = fields[1].parse().map_err(|e| format!("bad outcome {}: {}", fields[1], e))?;
+        let mut visits = vec![];
+        for word in fields[2].split_whitespace() {
+            let mut parts = word.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(cell), Some(share)) => {
+                    let pos = state.parse_cell(cell).map_err(|e| e.to_string())?;
+                    let share = share.parse().map_err(|e| format!("bad share {}: {}", word, e))?;
+                    visits.push((pos, share));
+                }
+                _ => return Err(format!("bad visit count: {}", word)),
+            }
+        }
+        Ok(ReplayEntry {
+            state: state,
+            visits: visits,
+            outcome: outcome,
+        })
+    }
+}
+
+impl fmt::Display for ReplayEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} | {} |", self.state.to_notation(), self.outcome)?;
+        for &(pos, share) in &self.visits {
+            write!(f, " {}:{}", self.state.cell(pos), share)?;
+        }
+        Ok(())
+    }
+}
+
+/// The most recent `capacity` replay entries; older ones are dropped.
+pub struct ReplayBuffer {
+    capacity: usize,
+    entries: VecDeque<ReplayEntry>,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> ReplayBuffer {
+        ReplayBuffer {
+            capacity: capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, entry: ReplayEntry) {
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &VecDeque<ReplayEntry> {
+        &self.entries
+    }
+
+    /// `n` entries chosen at random, with replacement.
+    pub fn sample<R: Rng>(&self, n: usize, rng: &mut R) -> Vec<&ReplayEntry> {
+        if self.entries.is_empty() {
+            return vec![];
+        }
+        (0 .. n).map(|_| &self.entries[rng.gen_range(0, self.entries.len())]).collect()
+    }
+
+    /// Reads a buffer saved by `save`, one entry per line.
+    pub fn load(path: &str, capacity: usize) -> Result<ReplayBuffer, String> {
+        let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+        let mut buffer = ReplayBuffer::new(capacity);
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("couldn't read {}: {}", path, e))?;
+            if !line.trim().is_empty() {
+                buffer.push(ReplayEntry::parse(&line).map_err(|e| format!("{}: {}", path, e))?);
+            }
+        }
+        Ok(buffer)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|e| format!("couldn't create {}: {}", path, e))?;
+        for entry in &self.entries {
+            writeln!(file, "{}", entry).map_err(|e| format!("couldn't write {}: {}", path, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays one game of `ai` against itself and returns it with an entry for
+/// every position.  For the first `temperature_moves` moves, moves are
+/// drawn in proportion to their visits so that games differ; after that the
+/// most visited move is played.
+pub fn zero_game<R: Rng>(ai: &ZeroAi, config: Config, temperature_moves: usize,
+                         rng: &mut R) -> (GameRecord, Vec<ReplayEntry>) {
+    let mut state = GameState::with_config(config);
+    let mut record = GameRecord::new("zero", "zero", config);
+    let mut searched = vec![];
+
+    let result = loop {
+        let start = Instant::now();
+        let visits = ai.visit_distribution(&state, rng);
+        let pos = if record.moves.len() < temperature_moves {
+            let mut x = rng.gen::<f32>();
+            let mut chosen = visits[visits.len() - 1].0;
+            for &(mv, share) in &visits {
+                if x < share {
+                    chosen = mv;
+                    break;
+                }
+                x -= share;
+            }
+            chosen
+        } else {
+            visits.iter().fold(visits[0], |best, &v| if v.1 > best.1 { v } else { best }).0
+        };
+        searched.push((state.clone(), visits));
+        record.push(pos, start.elapsed());
+        match state.make_move(&pos) {
+            MoveResult::Good => {}
+            result => break result,
+        }
+    };
+    record.result = Some(result);
+
+    let entries = searched.into_iter().map(|(state, visits)| {
+        let outcome = match result.winner() {
+            Some(p) if p == state.current_player() => 1.0,
+            Some(_) => -1.0,
+            None => 0.0,
+        };
+        ReplayEntry {
+            state: state,
+            visits: visits,
+            outcome: outcome,
+        }
+    }).collect();
+    (record, entries)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ZeroOptions {
+    pub steps: usize,
+    pub batch_size: usize,
+    pub learning_rate: f32,
+    /// Show each sampled entry under a random symmetry of the board.
+    pub symmetries: bool,
+}
+
+impl Default for ZeroOptions {
+    fn default() -> ZeroOptions {
+        ZeroOptions {
+            steps: 200,
+            batch_size: 32,
+            learning_rate: 0.01,
+            symmetries: true,
+        }
+    }
+}
+
+/// The mean squared error of `net`'s values and the mean cross entropy of
+/// its policy against the visit shares, over `entries`.
+pub fn zero_loss<'a, I: IntoIterator<Item=&'a ReplayEntry>>(net: &ZeroNet, entries: I) -> (f32, f32) {
+    let entries: Vec<_> = entries.into_iter().collect();
+    let (mut value, mut policy) = (0.0, 0.0);
+    for batch in entries.chunks(256) {
+        let states: Vec<_> = batch.iter().map(|entry| entry.state.clone()).collect();
+        for (entry, (priors, v)) in batch.iter().zip(net.evaluate_batch(&states)) {
+            value += (v - entry.outcome) * (v - entry.outcome);
+            for &(pos, share) in &entry.visits {
+                if let Some(&(_, p)) = priors.iter().find(|&&(mv, _)| mv == pos) {
+                    policy -= share * p.max(1e-6).ln();
+                }
+            }
+        }
+    }
+    let n = entries.len().max(1) as f32;
+    (value / n, policy / n)
+}
+
+/// Trains `net` on minibatches drawn from `buffer`, on the sum of the value
+/// error and the policy cross entropy.
+pub fn fit_zero<R: Rng>(net: &mut ZeroNet, buffer: &ReplayBuffer, options: ZeroOptions, rng: &mut R) {
+    let inputs = net.inputs();
+    let hidden = net.hidden();
+    let cells = net.cells().len();
+    let symmetries = Symmetry::all();
+
+    for _ in 0 .. options.steps {
+        let batch = buffer.sample(options.batch_size, rng);
+        if batch.is_empty() {
+            return;
+        }
+        let mut hidden_grad = Layer::new(inputs, hidden);
+        let mut policy_grad = Layer::new(hidden, cells);
+        let mut value_grad = Layer::new(hidden, 1);
+
+        for entry in &batch {
+            let symmetry = if options.symmetries { *rng.choose(&symmetries).unwrap() } else { Symmetry::identity() };
+            let state = symmetry.apply_state(&entry.state);
+            let a = net.forward(net.encode(&state));
+
+            let mut policy_error = vec![0.0f32; cells];
+            for (pos, p) in net.priors(&state, &a.policy) {
+                policy_error[net.index(&pos).unwrap()] += p;
+            }
+            for &(pos, share) in &entry.visits {
+                if let Some(i) = net.index(&symmetry.apply(&pos)) {
+                    policy_error[i] -= share;
+                }
+            }
+            let value_error = 2.0 * (a.value - entry.outcome) * (1.0 - a.value * a.value);
+
+            value_grad.bias[0] += value_error;
+            let mut hidden_error: Vec<f32> = (0 .. hidden).map(|j| value_error * net.value.weights[j]).collect();
+            for j in 0 .. hidden {
+                value_grad.weights[j] += value_error * a.hidden[j];
+            }
+            for (i, &error) in policy_error.iter().enumerate().filter(|&(_, &e)| e != 0.0) {
+                policy_grad.bias[i] += error;
+                for j in 0 .. hidden {
+                    policy_grad.weights[i * hidden + j] += error * a.hidden[j];
+                    hidden_error[j] += error * net.policy.weights[i * hidden + j];
+                }
+            }
+            for j in 0 .. hidden {
+                let error = hidden_error[j] * (1.0 - a.hidden[j] * a.hidden[j]);
+                hidden_grad.bias[j] += error;
+                for &k in &a.active {
+                    hidden_grad.weights[j * inputs + k] += error;
+                }
+            }
+        }
+
+        let step = options.learning_rate / batch.len() as f32;
+        net.hidden_layer.descend(&hidden_grad, step);
+        net.policy.descend(&policy_grad, step);
+        net.value.descend(&value_grad, step);
+    }
+}
@@ -0,0 +1,195 @@
+extern crate hexagon;
+extern crate hexgame;
+
+use hexgame::{Action, Config, GameState, Player, MoveResult};
+use hexgame::ai::*;
+use hexgame::ascii::render_ascii;
+use hexagon::HexPosition;
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+const USAGE: &'static str = "\
+usage: tui [--side red|green] [--ai SPEC] [--time MS] [--swap]
+
+--swap plays with the swap rule: on their first turn Green may play `swap`
+to take over Red's first stone.
+
+ai specs:
+    ranker:DEPTH[:WEIGHTS_FILE]
+    linear:DEPTH[:WEIGHTS_FILE]
+    mlp:DEPTH:WEIGHTS_FILE
+    null:DEPTH
+    mcts:ITERATIONS
+    zero:ITERATIONS:WEIGHTS_FILE
+    tablebase:FILE:SPEC
+
+commands:
+    <cell>    play a move, e.g. e5
+    swap      take over Red's first stone, if the swap rule allows it
+    resign    give up the game
+    draw      offer a draw, or accept the ai's offer
+    undo      take back your last move
+    hint      ask the ai for a move
+    new       start a new game
+    quit      leave";
+
+struct Options {
+    side: Player,
+    ai: String,
+    time: Option<Duration>,
+    swap: bool,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        side: Player::Red,
+        ai: "ranker:4".to_string(),
+        time: None,
+        swap: false,
+    };
+
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match &arg[..] {
+            "--side" => options.side = match &value()?[..] {
+                "red" => Player::Red,
+                "green" => Player::Green,
+                other => return Err(format!("unknown side: {}", other)),
+            },
+            "--ai" => options.ai = value()?,
+            "--time" => {
+                let ms = value()?.parse().map_err(|e| format!("bad time: {}", e))?;
+                options.time = Some(Duration::from_millis(ms));
+            }
+            "--swap" => options.swap = true,
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn ai_move(ai: &mut dyn Ai, game: &GameState, time: Option<Duration>) -> HexPosition {
+    match time {
+        Some(budget) => ai.choose_timed(game, game.current_player(), budget),
+        None => ai.choose(game, game.current_player()),
+    }
+}
+
+// Without a time limit the ai may also resign or take a draw.
+fn ai_action(ai: &mut dyn Ai, game: &GameState, time: Option<Duration>) -> Action {
+    match time {
+        Some(_) => Action::Place(ai_move(ai, game, time)),
+        None => ai.act(game, game.current_player()),
+    }
+}
+
+fn show(game: &GameState) {
+    println!("\n{}", render_ascii(game, true));
+    match game.is_over() {
+        MoveResult::End(p) => println!("{:?} wins.", p),
+        MoveResult::Tie => println!("Tie."),
+        MoveResult::Resigned(p) => println!("{:?} resigned.", p),
+        MoveResult::Draw => println!("Drawn by agreement."),
+        _ => {
+            if let Some(p) = game.draw_offer() {
+                println!("{:?} offers a draw.", p);
+            }
+            println!("{:?} to move.", game.current_player());
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            return;
+        }
+    };
+    let mut ai = match from_spec(&options.ai) {
+        Ok(ai) => ai,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            return;
+        }
+    };
+
+    let config = Config {
+        swap: options.swap,
+        .. Config::standard()
+    };
+    let mut history = vec![GameState::with_config(config)];
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        let game = history.last().unwrap().clone();
+        let over = game.is_game_over() || game.map().is_full();
+
+        if !over && game.current_player() != options.side {
+            let action = ai_action(&mut *ai, &game, options.time);
+            match action {
+                Action::Place(mv) => println!("ai plays {}", game.move_name(mv)),
+                Action::Swap => println!("ai plays swap"),
+                Action::Resign => println!("ai resigns"),
+                Action::OfferDraw => println!("ai offers a draw"),
+                Action::AcceptDraw => println!("ai accepts the draw"),
+            }
+            history.push(game.with_action(action));
+            continue;
+        }
+
+        show(&game);
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return,
+        };
+
+        match line.trim() {
+            "" => {}
+            "quit" | "exit" => return,
+            "help" => println!("{}", USAGE),
+            "new" => history.truncate(1),
+            "undo" => {
+                // Take back the ai's reply as well as our own move.
+                while history.len() > 1 {
+                    history.pop();
+                    if history.last().unwrap().current_player() == options.side {
+                        break;
+                    }
+                }
+            }
+            "hint" if !over => {
+                let mv = ai_move(&mut *ai, &game, options.time);
+                println!("hint: {}", game.move_name(mv));
+            }
+            _ if over => println!("The game is over; try `new` or `undo`."),
+            "resign" => history.push(game.with_action(Action::Resign)),
+            "draw" => {
+                let action = if game.draw_offer().is_some() { Action::AcceptDraw } else { Action::OfferDraw };
+                let mut next = game.clone();
+                match next.act(action) {
+                    MoveResult::Bad => println!("You have already offered a draw."),
+                    _ => history.push(next),
+                }
+            }
+            cell => match game.parse_move(cell) {
+                Ok(pos) => {
+                    let mut next = game.clone();
+                    match next.make_move(&pos) {
+                        MoveResult::Bad => println!("{} is not a legal move.", cell),
+                        _ => history.push(next),
+                    }
+                }
+                Err(e) => println!("{}", e),
+            },
+        }
+    }
+}
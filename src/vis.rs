@@ -2,14 +2,23 @@ extern crate hexagon;
 extern crate lux;
 extern crate hexgame;
 
-use hexgame::{GameState};
-use std::io::BufRead;
+use hexgame::{GameState, MoveResult};
+use hexgame::ai::HeatmapSource;
+use hexgame::net::{ClientMessage, ServerMessage};
+use hexgame::record::{self, GameRecord};
 use lux::prelude::*;
+use lux::interactive::Event;
 use lux::graphics::{ColorVertex, PrimitiveType, PrimitiveCanvas};
 use hexagon::*;
 use hexagon::screen::ScreenSpace;
 use hexagon::grid::Grid;
 
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
 fn draw_hex(frame: &mut Frame, screen: &ScreenSpace, hex: &HexPosition, color: [f32; 4], size: f32) {
     fn get_color_vertex((x, y): (f32, f32), color: [f32; 4]) -> ColorVertex {
         ColorVertex {
@@ -30,6 +39,12 @@ fn draw_hex(frame: &mut Frame, screen: &ScreenSpace, hex: &HexPosition, color: [
     ], None, None).unwrap();
 }
 
+fn draw_heatmap(frame: &mut Frame, screen: &ScreenSpace, heatmap: &[(HexPosition, [f32; 4])]) {
+    for &(tile, color) in heatmap {
+        draw_hex(frame, screen, &tile, color, 45.0);
+    }
+}
+
 fn render_game(frame: &mut Frame, state: &GameState, screen: &ScreenSpace) {
     let size = 45.0;
 
@@ -40,69 +55,295 @@ fn render_game(frame: &mut Frame, state: &GameState, screen: &ScreenSpace) {
     for (tile, player) in state.map().iter() {
         draw_hex(frame, screen, &tile, player.color(), size);
     }
+
+    if let Some(last) = state.last_move() {
+        let c = state.map().get(&last).unwrap().color();
+        draw_hex(frame, screen, &last, [c[0] * 0.6, c[1] * 0.6, c[2] * 0.6, c[3]], size * 0.6);
+    }
 }
 
-enum Command {
-    Move(HexPosition),
-    Reset,
+const USAGE: &'static str = "\
+usage: vis [--heatmap SPEC] [RECORD_FILE]
+       vis [--heatmap SPEC] --spectate ADDRESS [GAME]
+
+Replays the game records in RECORD_FILE, or as they arrive on stdin.  The
+window title shows the game, the move number, the result and the players.
+
+--spectate watches games live on a `server` at ADDRESS: game number GAME,
+or without one each game as it starts.
+
+--heatmap colours the empty cells by the opinion of an ai (ranker:DEPTH,
+mcts:ITERATIONS, ...), or by `status` to show winning, losing and
+must-block cells.
+
+keys:
+    l / h     step forward / back
+    0 / $     jump to the start / end of the game
+    n / p     next / previous game
+    space     play / pause
+    + / -     play faster / slower";
+
+enum Incoming {
+    Game(GameRecord),
+    // A new version of the last game, which is still being played.
+    Update(GameRecord),
+}
+
+fn send_records<R: BufRead>(reader: R, s: &Sender<Incoming>) {
+    for record in GameRecord::read(reader) {
+        match record {
+            Ok(record) => if s.send(Incoming::Game(record)).is_err() {
+                return;
+            },
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+fn spectate(address: &str, game: Option<usize>, s: &Sender<Incoming>) -> Result<(), String> {
+    let mut stream = TcpStream::connect(address).map_err(|e| format!("couldn't connect to {}: {}", address, e))?;
+    writeln!(stream, "{}", ClientMessage::Watch(game)).map_err(|e| e.to_string())?;
+
+    let mut record = None;
+    let mut last_move = Instant::now();
+    for line in BufReader::new(stream).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let incoming = match (ServerMessage::parse(&line), record.as_mut()) {
+            (Some(ServerMessage::Watching(_, red, green)), _) => {
+                record = Some(GameRecord::new(red, green, GameState::new().config()));
+                None
+            }
+            (Some(ServerMessage::Position(position)), Some(record)) => {
+                record.config = GameState::from_notation(&position).map_err(|e| e.to_string())?.config();
+                last_move = Instant::now();
+                Some(Incoming::Game(record.clone()))
+            }
+            (Some(ServerMessage::Moved(_, cell)), Some(record)) => {
+                let pos = record.final_state().parse_move(&cell).map_err(|e| e.to_string())?;
+                record.push(pos, last_move.elapsed());
+                last_move = Instant::now();
+                Some(Incoming::Update(record.clone()))
+            }
+            (Some(ServerMessage::Over(result, _)), Some(record)) => {
+                record.result = result;
+                Some(Incoming::Update(record.clone()))
+            }
+            (Some(ServerMessage::Error(e)), _) => return Err(e),
+            _ => None,
+        };
+        if let Some(incoming) = incoming {
+            if s.send(incoming).is_err() {
+                return Ok(());
+            }
+        }
+    }
+    Err("the server hung up".to_string())
+}
+
+struct Replay {
+    games: Vec<GameRecord>,
+    game: usize,
+    states: Vec<GameState>,
+    position: usize,
+}
+
+impl Replay {
+    fn select(&mut self, game: usize) {
+        self.game = game;
+        self.states = self.games[game].states();
+        self.position = 0;
+    }
+
+    // Replaces the last game, keeping our place if we're watching it.
+    fn update(&mut self, record: GameRecord) {
+        let last = self.games.len() - 1;
+        self.games[last] = record;
+        if self.game == last {
+            self.states = self.games[last].states();
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.position + 1 >= self.states.len()
+    }
+
+    fn step(&mut self, forward: bool) {
+        if forward && !self.at_end() {
+            self.position += 1;
+        } else if !forward && self.position > 0 {
+            self.position -= 1;
+        }
+    }
+
+    fn status(&self, playing: bool, delay: u64) -> String {
+        let record = &self.games[self.game];
+        let result = if self.at_end() {
+            record::result_to_str(record.result)
+        } else {
+            "*"
+        };
+        format!("game {}/{}  move {}/{}  result {}  {} ({}ms/move)  {} vs {}",
+                self.game + 1, self.games.len(),
+                self.position, self.states.len() - 1,
+                result,
+                if playing { "playing" } else { "paused" }, delay,
+                record.red, record.green)
+    }
 }
 
 fn main() {
     use std::sync::mpsc::channel;
     use std::thread::spawn;
-    let mut window = Window::new_with_defaults().unwrap();
-    let mut game = GameState::new();
 
-    let screenspace = ScreenSpace {
-        size: 50.0,
-        origin: (500.0, 500.0),
+    let mut path = None;
+    let mut address = None;
+    let mut heatmap_source = None;
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--heatmap" => match args.next().ok_or("missing heatmap".to_string()).and_then(|s| HeatmapSource::from_spec(&s)) {
+                Ok(source) => heatmap_source = Some(source),
+                Err(e) => {
+                    println!("{}\n{}", e, USAGE);
+                    return;
+                }
+            },
+            "--spectate" => address = args.next(),
+            _ if arg.starts_with('-') || path.is_some() => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => path = Some(arg),
+        }
+    }
+
+    let game = match (&address, &path) {
+        (&Some(_), &Some(ref game)) => match game.parse() {
+            Ok(game) => Some(game),
+            Err(_) => {
+                println!("{}", USAGE);
+                return;
+            }
+        },
+        _ => None,
     };
 
     let (s, r) = channel();
     spawn(move || {
-        let stdin = ::std::io::stdin();
-        let lines = stdin.lock().lines();
-        for line in lines {
-            let line = line.ok();
-            let mv = 
-                line.clone().and_then(|parts| {
-                    if parts.starts_with("new game") {
-                        Some(Command::Reset)
-                    } else {
-                        let mut parts = parts.split(",");
-                        parts.next().and_then(|part_1| {
-                            part_1.trim().parse().ok().and_then(|part_1| {
-                                parts.next().and_then(|part_2| {
-                                    part_2.trim().parse().ok().and_then(|part_2| {
-                                        Some(Command::Move(HexPosition::from_axial(part_1, part_2)))
-                                    })
-                                })
-                            })
-                        })
-                    }
-                });
-            if let Some(mv) = mv {
-                s.send(mv).unwrap();
-            } 
-            else {
-                println!("{}", line.unwrap());
+        if let Some(address) = address {
+            if let Err(e) = spectate(&address, game, &s) {
+                println!("{}", e);
+            }
+            return;
+        }
+        match path {
+            Some(path) => match File::open(&path) {
+                Ok(file) => send_records(BufReader::new(file), &s),
+                Err(e) => println!("couldn't open {}: {}", path, e),
+            },
+            None => {
+                let stdin = ::std::io::stdin();
+                send_records(stdin.lock(), &s);
             }
         }
     });
 
+    let mut window = Window::new_with_defaults().unwrap();
+    let screenspace = ScreenSpace {
+        size: 50.0,
+        origin: (500.0, 500.0),
+    };
+
+    let mut replay = Replay {
+        games: vec![],
+        game: 0,
+        states: vec![GameState::new()],
+        position: 0,
+    };
+    let mut playing = true;
+    let mut delay = 500;
+    let mut last_step = Instant::now();
+    let mut last_status = String::new();
+    let mut heatmap: Option<(GameState, Vec<_>)> = None;
+
     while window.is_open() {
+        while let Ok(incoming) = r.try_recv() {
+            match incoming {
+                Incoming::Game(record) => {
+                    replay.games.push(record);
+                    if replay.games.len() == 1 {
+                        replay.select(0);
+                    }
+                }
+                Incoming::Update(record) => replay.update(record),
+            }
+        }
+
+        for event in window.events() {
+            if let Event::KeyPressed(_, Some(c), _) = event {
+                let count = replay.games.len();
+                match c {
+                    'l' | '.' => replay.step(true),
+                    'h' | ',' => replay.step(false),
+                    '0' => replay.position = 0,
+                    '$' => replay.position = replay.states.len() - 1,
+                    'n' if replay.game + 1 < count => replay.select(replay.game + 1),
+                    'p' if replay.game > 0 => replay.select(replay.game - 1),
+                    ' ' => playing = !playing,
+                    '+' | '=' => delay = ::std::cmp::max(delay / 2, 25),
+                    '-' => delay = ::std::cmp::min(delay * 2, 4000),
+                    _ => {}
+                }
+                last_step = Instant::now();
+            }
+        }
+
+        if playing && last_step.elapsed() >= Duration::from_millis(delay) {
+            if !replay.at_end() {
+                replay.step(true);
+            } else if replay.game + 1 < replay.games.len() {
+                replay.select(replay.game + 1);
+            }
+            last_step = Instant::now();
+        }
+
+        if !replay.games.is_empty() {
+            let status = replay.status(playing, delay);
+            if status != last_status {
+                window.set_title(&status);
+                println!("{}", status);
+                last_status = status;
+            }
+        }
+
+        let state = &replay.states[replay.position];
         let mut frame = window.cleared_frame((0.0, 0.0, 0.0));
-        render_game(&mut frame, &game, &screenspace);
+        render_game(&mut frame, state, &screenspace);
+        if let Some(ref mut source) = heatmap_source {
+            if heatmap.as_ref().map(|&(ref cached, _)| cached != state).unwrap_or(true) {
+                heatmap = Some((state.clone(), source.heatmap(state)));
+            }
+            draw_heatmap(&mut frame, &screenspace, &heatmap.as_ref().unwrap().1);
+        }
 
-        match r.try_recv() {
-            Ok(Command::Move(mov)) => {
-                game.make_move(&mov);
-                ::std::thread::sleep_ms(500);
-            },
-            Ok(Command::Reset) => {
-                game = GameState::new();
-            },
-            Err(_) => {}
+        let result = match state.is_over() {
+            MoveResult::End(p) => Some(p.color()),
+            MoveResult::Tie => Some([1.0, 1.0, 1.0, 1.0]),
+            // Games lost on time end without a result on the board.
+            _ if replay.at_end() && !replay.games.is_empty() => {
+                replay.games[replay.game].result.and_then(|r| r.winner()).map(|p| p.color())
+            }
+            _ => None,
+        };
+        if let Some(color) = result {
+            frame.draw(Rectangle {
+                x: 0.0,
+                y: 0.0,
+                w: 50.0,
+                h: 50.0,
+                color: color,
+                .. Default::default()
+            }).unwrap();
         }
     }
 }
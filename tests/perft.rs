@@ -0,0 +1,84 @@
+extern crate hexgame;
+
+use hexgame::{Config, GameState};
+use hexgame::counts::{divide, perft, Counts};
+
+fn empty(radius: u32, swap: bool) -> GameState {
+    GameState::with_config(Config { radius: radius, swap: swap })
+}
+
+fn position(notation: &str) -> GameState {
+    GameState::from_notation(notation).unwrap()
+}
+
+#[test]
+fn tiny_board() {
+    assert_eq!(perft(&empty(1, false), 8), vec![
+        Counts::new(7, 0, 0, 0),
+        Counts::new(42, 0, 0, 0),
+        Counts::new(210, 0, 0, 0),
+        Counts::new(840, 0, 0, 0),
+        Counts::new(2520, 0, 216, 0),
+        Counts::new(4608, 0, 432, 0),
+        Counts::new(4176, 0, 1296, 2880),
+        Counts::new(0, 0, 0, 0),
+    ]);
+}
+
+#[test]
+fn tiny_board_with_swap() {
+    assert_eq!(perft(&empty(1, true), 7), vec![
+        Counts::new(7, 0, 0, 0),
+        Counts::new(49, 0, 0, 0),
+        Counts::new(252, 0, 0, 0),
+        Counts::new(1050, 0, 0, 0),
+        Counts::new(3360, 0, 216, 0),
+        Counts::new(7128, 0, 648, 0),
+        Counts::new(8784, 0, 1728, 2880),
+    ]);
+}
+
+#[test]
+fn standard_board() {
+    assert_eq!(perft(&empty(4, false), 3), vec![
+        Counts::new(61, 0, 0, 0),
+        Counts::new(3660, 0, 0, 0),
+        Counts::new(215940, 0, 0, 0),
+    ]);
+    assert_eq!(perft(&empty(4, true), 2)[1], Counts::new(3721, 0, 0, 0));
+}
+
+#[test]
+fn middle_game() {
+    let state = position("5/6/r6/rr2gg2/1r4g2/5rr1/2gg2r/2g3/5 g 4 f7");
+    assert_eq!(perft(&state, 3), vec![
+        Counts::new(48, 0, 9, 0),
+        Counts::new(1833, 0, 343, 0),
+        Counts::new(68540, 336, 13946, 0),
+    ]);
+}
+
+#[test]
+fn end_game() {
+    let state = position("rrggr/rgrrgg/grggrrg/g1grggrr/rgrgrrggr/g1rrgrrg/grg1rg1/1rrggr/1gr1g r 4 i6");
+    assert_eq!(perft(&state, 6), vec![
+        Counts::new(7, 0, 3, 0),
+        Counts::new(24, 0, 15, 0),
+        Counts::new(45, 0, 27, 0),
+        Counts::new(72, 6, 54, 0),
+        Counts::new(36, 0, 36, 0),
+        Counts::new(0, 0, 0, 0),
+    ]);
+}
+
+#[test]
+fn divide_adds_up() {
+    let state = position("5/6/r6/rr2gg2/1r4g2/5rr1/2gg2r/2g3/5 g 4 f7");
+    for depth in 1 .. 4 {
+        let mut total = Counts::default();
+        for (_, counts) in divide(&state, depth) {
+            total.add(&counts);
+        }
+        assert_eq!(total, perft(&state, depth)[depth - 1]);
+    }
+}
@@ -0,0 +1,249 @@
+//! Randomized checks of the rules over many random games.
+
+extern crate hexagon;
+extern crate hexgame;
+extern crate rand;
+
+use hexagon::HexPosition;
+use hexgame::{Action, Config, GameState, MoveResult, Player};
+use hexgame::symmetry::Symmetry;
+use hexgame::training::seeded_rng;
+use rand::Rng;
+
+const GAMES: usize = 40;
+
+fn configs() -> Vec<Config> {
+    let mut configs = vec![];
+    for radius in 2 .. 5 {
+        for &swap in &[false, true] {
+            configs.push(Config { radius: radius, swap: swap });
+        }
+    }
+    configs
+}
+
+fn on_board(q: i32, r: i32, radius: i32) -> bool {
+    q.abs() <= radius && r.abs() <= radius && (q + r).abs() <= radius
+}
+
+fn stone(state: &GameState, q: i32, r: i32) -> Option<Player> {
+    if on_board(q, r, state.config().radius as i32) {
+        state.map().get(&HexPosition::from_axial(q, r)).cloned()
+    } else {
+        None
+    }
+}
+
+fn stones(state: &GameState) -> Vec<(HexPosition, Player)> {
+    state.map().iter().map(|(pos, &player)| (pos, player)).collect()
+}
+
+// The result of looking at every line on the board rather than just the ones
+// through the last move.  Only the player who moved last can have a line in
+// a game played by the rules, as any earlier line would have ended it.
+fn full_scan(state: &GameState) -> MoveResult {
+    let radius = state.config().radius as i32;
+    let mut cells = 0;
+    let mut filled = 0;
+    let mut three = None;
+    let mut four = None;
+    for q in -radius .. radius + 1 {
+        for r in -radius .. radius + 1 {
+            if !on_board(q, r, radius) {
+                continue;
+            }
+            cells += 1;
+            let player = match stone(state, q, r) {
+                Some(player) => player,
+                None => continue,
+            };
+            filled += 1;
+            for &(dq, dr) in &[(1, 0), (0, 1), (1, -1)] {
+                // Only count each run from its first stone.
+                if stone(state, q - dq, r - dr) == Some(player) {
+                    continue;
+                }
+                let mut run = 1;
+                while stone(state, q + run * dq, r + run * dr) == Some(player) {
+                    run += 1;
+                }
+                if run >= 4 {
+                    four = Some(player);
+                } else if run == 3 {
+                    three = Some(player);
+                }
+            }
+        }
+    }
+    if let Some(player) = four {
+        MoveResult::End(player)
+    } else if let Some(player) = three {
+        MoveResult::End(player.inverse())
+    } else if filled == cells {
+        MoveResult::Tie
+    } else {
+        MoveResult::Good
+    }
+}
+
+// A random game, given as every position in it.  Half the moves avoid
+// losing on the spot when they can, so that games last long enough to see
+// wins and full boards as well as losses.
+fn random_game<R: Rng>(config: Config, rng: &mut R) -> Vec<GameState> {
+    let mut states = vec![GameState::with_config(config)];
+    loop {
+        let state = states.last().unwrap().clone();
+        if state.is_over() != MoveResult::Good {
+            return states;
+        }
+        let moves = state.legal_moves();
+        let safe: Vec<_> = moves.iter().cloned().filter(|mv| {
+            state.with_move(mv).is_over() != MoveResult::End(state.current_player().inverse())
+        }).collect();
+        let mv = if rng.gen() && !safe.is_empty() {
+            *rng.choose(&safe).unwrap()
+        } else {
+            *rng.choose(&moves).unwrap()
+        };
+        states.push(state.with_move(&mv));
+    }
+}
+
+// Every position of every game, with the move that led to it.
+fn each_move<F: FnMut(&GameState, HexPosition, &GameState)>(seed: usize, mut check: F) {
+    let mut rng = seeded_rng(seed);
+    for config in configs() {
+        for _ in 0 .. GAMES {
+            let states = random_game(config, &mut rng);
+            for pair in states.windows(2) {
+                check(&pair[0], pair[1].last_move().unwrap(), &pair[1]);
+            }
+        }
+    }
+}
+
+#[test]
+fn stones_are_never_overwritten() {
+    each_move(1, |before, mv, after| {
+        let swap = before.is_swap(&mv);
+        for (pos, player) in stones(before) {
+            let expected = if swap { Player::Green } else { player };
+            assert_eq!(after.map().get(&pos), Some(&expected));
+            if !before.is_swap(&pos) {
+                let mut copy = before.clone();
+                assert_eq!(copy.make_move(&pos), MoveResult::Bad);
+                assert!(copy == *before);
+            }
+        }
+        let added = if swap { 0 } else { 1 };
+        assert_eq!(stones(after).len(), stones(before).len() + added);
+        assert_eq!(after.map().get(&mv), Some(&before.current_player()));
+    });
+}
+
+#[test]
+fn players_alternate() {
+    each_move(2, |before, mv, after| {
+        assert_eq!(after.current_player(), before.current_player().inverse());
+        assert!(after.last_move() == Some(mv));
+        let count = |state: &GameState, player| stones(state).iter().filter(|s| s.1 == player).count();
+        let mover = before.current_player();
+        if before.is_swap(&mv) {
+            // Green takes over Red's only stone.
+            assert_eq!((count(after, Player::Red), count(after, Player::Green)), (0, 1));
+        } else {
+            assert_eq!(count(after, mover), count(before, mover) + 1);
+            assert_eq!(count(after, mover.inverse()), count(before, mover.inverse()));
+        }
+    });
+}
+
+#[test]
+fn is_over_matches_a_full_scan() {
+    let (mut wins, mut losses, mut ties) = (0, 0, 0);
+    each_move(3, |_, mv, after| {
+        let result = after.is_over();
+        assert_eq!(result, full_scan(after));
+        let mover = after.map().get(&mv).cloned();
+        match result {
+            MoveResult::End(p) if Some(p) == mover => wins += 1,
+            MoveResult::End(_) => losses += 1,
+            MoveResult::Tie => ties += 1,
+            _ => {}
+        }
+    });
+    assert!(wins > 0 && losses > 0 && ties > 0, "{} wins, {} losses, {} ties", wins, losses, ties);
+}
+
+#[test]
+fn symmetric_positions_give_symmetric_outcomes() {
+    each_move(4, |before, mv, after| {
+        for symmetry in Symmetry::all() {
+            let image = symmetry.apply_state(before);
+            assert_eq!(image.is_over(), before.is_over());
+            let moved = image.with_move(&symmetry.apply(&mv));
+            assert_eq!(moved.to_notation(), symmetry.apply_state(after).to_notation());
+            assert_eq!(moved.is_over(), after.is_over());
+        }
+    });
+}
+
+#[test]
+fn with_move_leaves_the_original_alone() {
+    let mut rng = seeded_rng(5);
+    each_move(5, |before, mv, after| {
+        for state in &[before, after] {
+            let copy = (*state).clone();
+            let cells: Vec<_> = stones(state).into_iter().map(|s| s.0).collect();
+            let mut tries = vec![mv];
+            tries.extend(rng.choose(&state.legal_moves()));
+            tries.extend(rng.choose(&cells));
+            for pos in &tries {
+                state.with_move(pos);
+                assert!(**state == copy);
+            }
+        }
+    });
+}
+
+#[test]
+fn illegal_moves_leave_a_draw_offer_standing() {
+    let mut state = GameState::with_config(Config::standard());
+    state.make_move(&HexPosition::from_axial(0, 0));
+    assert_eq!(state.act(Action::OfferDraw), MoveResult::Good);
+    state.make_move(&HexPosition::from_axial(1, 0));
+    // Red to answer Green's offer.
+    let offer = state.draw_offer();
+    assert_eq!(offer, Some(Player::Green));
+    for pos in &[HexPosition::from_axial(0, 0), HexPosition::from_axial(9, 0)] {
+        assert_eq!(state.make_move(pos), MoveResult::Bad);
+        assert_eq!(state.draw_offer(), offer);
+    }
+}
+
+#[test]
+fn nothing_is_accepted_after_the_game_ends() {
+    let mut rng = seeded_rng(6);
+    for config in configs() {
+        let end = random_game(config, &mut rng).pop().unwrap();
+        let result = end.is_over();
+        let mut actions = vec![Action::Resign, Action::OfferDraw, Action::AcceptDraw, Action::Swap];
+        actions.extend(end.legal_moves().into_iter().map(Action::Place));
+        for action in actions {
+            let mut state = end.clone();
+            assert_eq!(state.act(action), MoveResult::Bad);
+            assert_eq!(state.is_over(), result);
+        }
+    }
+}
+
+#[test]
+fn a_line_on_the_last_cell_counts() {
+    // Red's only move fills the board and makes three in the middle row.
+    let state = GameState::from_notation("gr/rr1/gg r 1 c3").unwrap();
+    let last = state.parse_cell("b3").unwrap();
+    let after = state.with_move(&last);
+    assert!(after.map().is_full());
+    assert_eq!(after.is_over(), MoveResult::End(Player::Green));
+    assert_eq!(full_scan(&after), MoveResult::End(Player::Green));
+}
@@ -0,0 +1,22 @@
+extern crate hexgame;
+
+use hexgame::{Action, GameState};
+use hexgame::ai::from_spec;
+use std::time::Duration;
+
+// Green to move, and every empty cell makes three in a row for Green.
+const ALL_LOSE: &'static str = "gg1/grr1/rr1gr/g1gr/rrg g 2 c1";
+
+#[test]
+fn plays_a_move_when_every_move_loses() {
+    let state = GameState::from_notation(ALL_LOSE).unwrap();
+    let player = state.current_player();
+    for depth in 1 .. 4 {
+        let mut ai = from_spec(&format!("ranker:{}", depth)).unwrap();
+        assert!(state.is_legal(&ai.choose(&state, player)));
+        assert!(state.is_legal(&ai.choose_timed(&state, player, Duration::from_millis(100))));
+        if let Action::Place(mv) = ai.act(&state, player) {
+            assert!(state.is_legal(&mv));
+        }
+    }
+}
@@ -0,0 +1,45 @@
+extern crate hexgame;
+
+use hexgame::ai::from_spec;
+use hexgame::puzzle::{Puzzle, PuzzleKind};
+use hexgame::suite::{self, SuiteEntry};
+
+// The puzzle an entry was made from, going by its group: `winN` or `trap`.
+fn puzzle(entry: &SuiteEntry) -> Puzzle {
+    assert!(entry.best.len() == 1, "{}: expected a single bm", entry.id);
+    let kind = match entry.group() {
+        "trap" => PuzzleKind::AvoidTrap,
+        group if group.starts_with("win") => match group[3 ..].parse() {
+            Ok(moves) => PuzzleKind::WinIn(moves),
+            Err(_) => panic!("{}: bad group {}", entry.id, group),
+        },
+        group => panic!("{}: unknown group {}", entry.id, group),
+    };
+    Puzzle {
+        state: entry.state.clone(),
+        kind: kind,
+        solution: entry.best[0],
+    }
+}
+
+#[test]
+fn suites_are_sound() {
+    for path in &["suites/tactics.epd", "suites/fast.epd"] {
+        for entry in suite::load(path).unwrap() {
+            for mv in &entry.avoid {
+                assert!(entry.state.is_legal(mv) && !entry.best.contains(mv), "{}: bad am move", entry.id);
+            }
+            if let Err(e) = puzzle(&entry).verify() {
+                panic!("{}: {}", entry.id, e);
+            }
+        }
+    }
+}
+
+#[test]
+fn ranker_passes_fast_suite() {
+    let entries = suite::load("suites/fast.epd").unwrap();
+    let mut ai = from_spec("ranker:3").unwrap();
+    let report = suite::run(&mut *ai, &entries, None);
+    assert!(report.pass_rate() >= 0.9, "{}, failed {:?}", report, report.failures);
+}